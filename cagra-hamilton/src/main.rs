@@ -4,7 +4,7 @@ extern crate cagra;
 use failure::Error;
 
 fn main() -> Result<(), Error> {
-    let mut g = graph!(f64, {
+    let g = graph!(f64, {
         let q = 1.0;
         let p = 1.0;
         let h = square(q) + square(p);
@@ -12,24 +12,26 @@ fn main() -> Result<(), Error> {
     let q = g.get_index("q");
     let p = g.get_index("p");
     let h = g.get_index("h");
+    let mut tape = g.compile(&[h])?;
 
     let dt = 0.01;
     println!("t,E,q,p"); // csv header
     for t in 0..1000 {
-        let e = g.eval_value(h)?;
-        g.eval_deriv(h)?;
+        tape.forward()?;
+        tape.backward(h)?;
+        let e = tape.get_value(h)?;
 
-        let vp = g.get_value(p)?;
-        let hq = g.get_deriv(q)?;
+        let vp = tape.get_value(p)?;
+        let hq = tape.get_deriv(q)?;
 
-        g.set_value(p, vp.clone() - dt * hq)?;
+        tape.set_value(p, vp.clone() - dt * hq)?;
 
-        g.eval_value(h)?;
-        g.eval_deriv(h)?;
+        tape.forward()?;
+        tape.backward(h)?;
 
-        let vq = g.get_value(q)?;
-        let hp = g.get_deriv(p)?;
-        g.set_value(q, vq.clone() + dt * hp)?;
+        let vq = tape.get_value(q)?;
+        let hp = tape.get_deriv(p)?;
+        tape.set_value(q, vq.clone() + dt * hp)?;
 
         println!("{:.09},{:.09},{:.09},{:.09}", dt * t as f64, e, vq, vp);
     }
@@ -26,7 +26,7 @@ pub fn graph_impl(item: TokenStream) -> TokenStream {
     let ty = &input.ty;
     let lines = &input.block.stmts;
     let stmts: Vec<_> = lines
-        .into_iter()
+        .iter()
         .map(|line| match line {
             syn::Stmt::Local(local) => {
                 // lhs of `=`
@@ -38,7 +38,7 @@ pub fn graph_impl(item: TokenStream) -> TokenStream {
 
                 // rhs of `=`
                 let (_eq, expr) = local.init.as_ref().unwrap();
-                let (dep, expr) = quote_expr(&expr, &name);
+                let (dep, expr) = quote_expr(expr, &name);
                 quote! {
                     #(#dep)*
                     let #id = #expr;
@@ -74,7 +74,7 @@ fn quote_expr(expr: &syn::Expr, name: &str) -> (Vec<TokenStream2>, TokenStream2)
             }
             let f = &call.func;
             let f = quote!( #f );
-            let id = syn::Ident::new(&name, proc_macro2::Span::call_site());
+            let id = syn::Ident::new(name, proc_macro2::Span::call_site());
             ts.push(quote! { let #id = g.#f(#(#args),*); });
             (ts, quote! { #id })
         }
@@ -1,16 +1,21 @@
-use cagra::graph::*;
+use cagra::{graph::*, tensor::*};
 use criterion::{criterion_group, criterion_main, Criterion};
 
 // x = x + 1 + 1 + ...
 fn linear(c: &mut Criterion) {
     c.bench_function("eval_value", |b| {
         let mut g: Graph<f64> = Graph::new();
-        let mut x = g.scalar("x", 0.0).unwrap();
+        let x0 = g.scalar("x", 0.0).unwrap();
+        let mut x = x0;
         for _ in 0..1000 {
             let v = g.constant_scalar(1.0);
             x = g.add(x, v);
         }
-        b.iter(|| g.eval_value(x))
+        b.iter(|| {
+            // discard the cached values
+            g.set_value(x0, 0.0.into_tensor()).unwrap();
+            g.eval_value(x)
+        })
     });
 
     c.bench_function("eval_deriv", |b| {
@@ -23,6 +28,33 @@ fn linear(c: &mut Criterion) {
         g.eval_value(x).unwrap();
         b.iter(|| g.eval_deriv(x))
     });
+
+    c.bench_function("tape_forward", |b| {
+        let mut g: Graph<f64> = Graph::new();
+        let x0 = g.scalar("x", 0.0).unwrap();
+        let mut x = x0;
+        for _ in 0..1000 {
+            let v = g.constant_scalar(1.0);
+            x = g.add(x, v);
+        }
+        let mut tape = g.compile(&[x]).unwrap();
+        b.iter(|| {
+            tape.set_value(x0, 0.0.into_tensor()).unwrap();
+            tape.forward()
+        })
+    });
+
+    c.bench_function("tape_backward", |b| {
+        let mut g: Graph<f64> = Graph::new();
+        let mut x = g.scalar("x", 0.0).unwrap();
+        for _ in 0..1000 {
+            let v = g.constant_scalar(1.0);
+            x = g.add(x, v);
+        }
+        let mut tape = g.compile(&[x]).unwrap();
+        tape.forward().unwrap();
+        b.iter(|| tape.backward(x))
+    });
 }
 
 criterion_group!(benches, linear);
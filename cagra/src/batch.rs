@@ -0,0 +1,257 @@
+//! Batched evaluation over many input sets
+//!
+//! Inputs are stacked along a new leading (batch) axis, and every operator is applied
+//! to the whole batch at once. Nodes which are not batched, e.g. constants and
+//! variables without batched inputs, are broadcast along the batch axis.
+//! The graph itself is not modified.
+
+use cauchy::Scalar;
+use ndarray::{Axis, IxDyn};
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Property, Tensor};
+use crate::operator::{Binary, Unary};
+
+impl<A: Scalar> Graph<A> {
+    /// Evaluate the values of the ancestors of the node for stacked inputs
+    fn forward_batch(
+        &self,
+        node: NodeIndex,
+        inputs: &[(NodeIndex, Tensor<A>)],
+    ) -> Result<HashMap<NodeIndex, Tensor<A>>> {
+        let batch_size = batch_size(inputs)?;
+        let mut values: HashMap<NodeIndex, Tensor<A>> = HashMap::new();
+        for &(var, ref value) in inputs {
+            if !self[var].is_variable() {
+                return Err(Error::NodeTypeError { index: var.index() });
+            }
+            values.insert(var, value.clone());
+        }
+        for idx in self.topological_order(node) {
+            if values.contains_key(&idx) {
+                continue;
+            }
+            let value = match self[idx].property {
+                Property::Variable | Property::Constant => {
+                    let value = self.get_value(idx)?.insert_axis(Axis(0));
+                    let mut shape = value.shape().to_vec();
+                    shape[0] = batch_size;
+                    broadcast(&value, &shape)?
+                }
+                Property::Unary(op) => op.eval_value(values[&self.get_arg1(idx)].clone()),
+                Property::Binary(op) => {
+                    let (lhs, rhs) = self.get_arg2(idx);
+                    let lv = values[&lhs].clone();
+                    let rv = broadcast_sample(&values[&rhs], lv.shape())?;
+                    match op {
+                        Binary::Dot => sum_sample(lv * rv),
+                        _ => op.eval_value(lv, rv),
+                    }
+                }
+                Property::Custom(id) => {
+                    // custom operators are applied sample by sample
+                    let args: Vec<Vec<Tensor<A>>> = self
+                        .args(idx)
+                        .iter()
+                        .map(|arg| unstack(&values[arg]))
+                        .collect();
+                    let samples: Vec<Tensor<A>> = (0..batch_size)
+                        .map(|i| {
+                            let args: Vec<Tensor<A>> = args.iter().map(|a| a[i].clone()).collect();
+                            self.operators[id].eval_value(&args)
+                        })
+                        .collect();
+                    stack(&samples)?
+                }
+            };
+            values.insert(idx, value);
+        }
+        Ok(values)
+    }
+
+    /// Evaluate the value of the node for the inputs stacked along the leading axis.
+    ///
+    /// The result has the shape `[batch_size, ...node.shape]`.
+    pub fn eval_value_batch(
+        &self,
+        node: NodeIndex,
+        inputs: &[(NodeIndex, Tensor<A>)],
+    ) -> Result<Tensor<A>> {
+        let mut values = self.forward_batch(node, inputs)?;
+        Ok(values.remove(&node).unwrap())
+    }
+
+    /// Evaluate the per-sample derivatives of the node for the inputs stacked along
+    /// the leading axis.
+    ///
+    /// Returns the map from every variable on which the node depends to its derivative
+    /// of the shape `[batch_size, ...var.shape]`, including the variables not batched.
+    /// Variables reached only through `stop_gradient` are not contained.
+    pub fn eval_deriv_batch(
+        &self,
+        node: NodeIndex,
+        inputs: &[(NodeIndex, Tensor<A>)],
+    ) -> Result<HashMap<NodeIndex, Tensor<A>>> {
+        let values = self.forward_batch(node, inputs)?;
+        let mut derivs: HashMap<NodeIndex, Tensor<A>> = HashMap::new();
+        derivs.insert(node, Tensor::ones(values[&node].shape()));
+        for idx in self.topological_order(node).into_iter().rev() {
+            let der = match derivs.get(&idx) {
+                Some(der) => der.clone(),
+                None => continue,
+            };
+            let propagated = match self[idx].property {
+                Property::Variable | Property::Constant => continue,
+                Property::Unary(Unary::StopGradient) => continue,
+                Property::Unary(op) => {
+                    let arg = self.get_arg1(idx);
+                    vec![(arg, op.eval_deriv(values[&arg].clone(), der))]
+                }
+                Property::Binary(op) => {
+                    let (lhs, rhs) = self.get_arg2(idx);
+                    let lv = values[&lhs].clone();
+                    let r_shape = values[&rhs].shape();
+                    let rv = broadcast_sample(&values[&rhs], lv.shape())?;
+                    let (l_der, r_der) = match op {
+                        Binary::Dot => {
+                            let der = broadcast_sample(&der, lv.shape())?;
+                            (rv * der.clone(), lv * der)
+                        }
+                        _ => op.eval_deriv(lv, rv, der),
+                    };
+                    vec![(lhs, l_der), (rhs, reduce_sample(r_der, r_shape))]
+                }
+                Property::Custom(id) => {
+                    let args = self.args(idx);
+                    let arg_samples: Vec<Vec<Tensor<A>>> =
+                        args.iter().map(|arg| unstack(&values[arg])).collect();
+                    let mut ders: Vec<Vec<Tensor<A>>> = vec![Vec::new(); args.len()];
+                    for (i, der) in unstack(&der).into_iter().enumerate() {
+                        let sample: Vec<Tensor<A>> =
+                            arg_samples.iter().map(|a| a[i].clone()).collect();
+                        for (k, d) in self.operators[id]
+                            .eval_deriv(&sample, der)
+                            .into_iter()
+                            .enumerate()
+                        {
+                            ders[k].push(d);
+                        }
+                    }
+                    args.into_iter()
+                        .zip(ders.iter())
+                        .map(|(arg, ders)| Ok((arg, stack(ders)?)))
+                        .collect::<Result<Vec<_>>>()?
+                }
+            };
+            for (arg, der) in propagated {
+                let der = match derivs.remove(&arg) {
+                    Some(der_last) => der_last + der,
+                    None => der,
+                };
+                derivs.insert(arg, der);
+            }
+        }
+        Ok(derivs
+            .into_iter()
+            .filter(|(n, _)| self[*n].is_variable())
+            .collect())
+    }
+}
+
+/// Common length of the leading axes of the inputs
+fn batch_size<A: Scalar>(inputs: &[(NodeIndex, Tensor<A>)]) -> Result<usize> {
+    let mut size = None;
+    for (_, value) in inputs {
+        if value.ndim() == 0 {
+            return Err(Error::TensorRankMismatch {
+                actual: 0,
+                desired: 1,
+            });
+        }
+        let n = value.shape()[0];
+        match size {
+            Some(size) if size != n => {
+                return Err(Error::TensorShapeMismatch {
+                    actual: vec![n],
+                    desired: vec![size],
+                });
+            }
+            _ => size = Some(n),
+        }
+    }
+    Ok(size.unwrap_or(1))
+}
+
+fn broadcast<A: Scalar>(t: &Tensor<A>, shape: &[usize]) -> Result<Tensor<A>> {
+    match t.broadcast(IxDyn(shape)) {
+        Some(view) => Ok(view.to_owned().into_shared()),
+        None => Err(Error::TensorShapeMismatch {
+            actual: t.shape().to_vec(),
+            desired: shape.to_vec(),
+        }),
+    }
+}
+
+/// Broadcast the stacked tensor `[n, ...s]` to `[n, ...shape]` sample by sample
+fn broadcast_sample<A: Scalar>(t: &Tensor<A>, shape: &[usize]) -> Result<Tensor<A>> {
+    if t.shape() == shape {
+        return Ok(t.clone());
+    }
+    let mut t = t.clone();
+    while t.ndim() < shape.len() {
+        t = t.insert_axis(Axis(1));
+    }
+    broadcast(&t, shape)
+}
+
+/// Inverse of `broadcast_sample`, i.e. sum up the derivative `[n, ...]` into `[n, ...shape]`
+fn reduce_sample<A: Scalar>(der: Tensor<A>, shape: &[usize]) -> Tensor<A> {
+    if der.shape() == shape {
+        return der;
+    }
+    let mut der = der.to_owned();
+    while der.ndim() > shape.len() {
+        der = der.sum_axis(Axis(1));
+    }
+    for (i, &s) in shape.iter().enumerate() {
+        if s == 1 && der.shape()[i] != 1 {
+            der = der.sum_axis(Axis(i)).insert_axis(Axis(i));
+        }
+    }
+    der.into_shared()
+}
+
+/// Split the stacked tensor `[n, ...s]` into `n` samples of the shape `s`
+fn unstack<A: Scalar>(t: &Tensor<A>) -> Vec<Tensor<A>> {
+    t.outer_iter().map(|s| s.to_owned().into_shared()).collect()
+}
+
+/// Inverse of `unstack`
+fn stack<A: Scalar>(samples: &[Tensor<A>]) -> Result<Tensor<A>> {
+    if samples.is_empty() {
+        return Ok(Tensor::zeros(IxDyn(&[0])));
+    }
+    let shape = samples[0].shape();
+    if let Some(s) = samples.iter().find(|s| s.shape() != shape) {
+        return Err(Error::TensorShapeMismatch {
+            actual: s.shape().to_vec(),
+            desired: shape.to_vec(),
+        });
+    }
+    let views: Vec<_> = samples
+        .iter()
+        .map(|s| s.view().insert_axis(Axis(0)))
+        .collect();
+    Ok(ndarray::stack(Axis(0), &views).unwrap().into_shared())
+}
+
+/// Sum over every axis except the batch axis
+fn sum_sample<A: Scalar>(t: Tensor<A>) -> Tensor<A> {
+    let mut t = t.to_owned();
+    while t.ndim() > 1 {
+        t = t.sum_axis(Axis(1));
+    }
+    t.into_shared()
+}
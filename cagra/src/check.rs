@@ -0,0 +1,252 @@
+//! Gradient check by finite differences
+//!
+//! The derivative of the sum of the output elements given by `eval_deriv` is compared
+//! with the numerical one of every variable element, which is evaluated by
+//! the central difference `(f(x + h) - f(x - h)) / 2h` or
+//! the complex step `Im f(x + ih) / h`.
+//! The complex step is free from the cancellation error, and thus a tiny step
+//! such as `1e-20` can be used, but it is available only for real graphs
+//! consisting of built-in operators.
+//!
+//! ```
+//! use cagra::{check::*, graph::*};
+//!
+//! let mut g: Graph<f64> = Graph::new();
+//! let x = g.scalar("x", 0.3).unwrap();
+//! let y = g.tan(x);
+//! let report = check_complex_step(&g, y, 1e-20).unwrap();
+//! assert!(report.is_within(1e-12));
+//! ```
+
+use cauchy::Scalar;
+use ndarray::ArrayD;
+use num_traits::Float;
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Property, Tensor};
+use crate::operator::{Binary, Unary};
+
+/// Errors of the derivative with respect to a variable
+#[derive(Debug, Clone)]
+pub struct VariableError<A: Scalar> {
+    pub index: NodeIndex,
+    pub name: Option<String>,
+    /// Maximum of the absolute errors over the elements,
+    /// which is infinity if either derivative has a NaN or infinite element
+    pub abs_error: A::Real,
+    /// Maximum of the relative errors over the elements, infinity in the same case
+    pub rel_error: A::Real,
+}
+
+/// Result of the gradient check
+#[derive(Debug, Clone)]
+pub struct Report<A: Scalar> {
+    /// Errors of each variable in the order of the indices
+    pub variables: Vec<VariableError<A>>,
+}
+
+impl<A: Scalar> Report<A> {
+    /// Returns true if either of the absolute or relative error is within
+    /// the tolerance for every variable
+    pub fn is_within(&self, tol: A::Real) -> bool {
+        self.variables
+            .iter()
+            .all(|e| e.abs_error <= tol || e.rel_error <= tol)
+    }
+
+    pub fn max_abs_error(&self) -> A::Real {
+        self.variables
+            .iter()
+            .fold(A::real(0.0), |m, e| max(m, e.abs_error))
+    }
+
+    pub fn max_rel_error(&self) -> A::Real {
+        self.variables
+            .iter()
+            .fold(A::real(0.0), |m, e| max(m, e.rel_error))
+    }
+}
+
+/// Derivatives of the sum of the output elements by the central difference
+/// with respect to all variables
+pub fn central_difference<A: Scalar>(
+    g: &Graph<A>,
+    output: NodeIndex,
+    step: A::Real,
+) -> Result<HashMap<NodeIndex, Tensor<A>>> {
+    let mut g = g.clone();
+    let h = A::from_real(step);
+    let two_h = A::from_real(step + step);
+    let mut derivs = HashMap::new();
+    for var in g.variables() {
+        let value = g.get_value(var)?;
+        let mut deriv = Vec::with_capacity(value.len());
+        for i in 0..value.len() {
+            g.set_value(var, perturbed(&value, i, h))?;
+            let plus = g.eval_value(output)?.sum();
+            g.set_value(var, perturbed(&value, i, -h))?;
+            let minus = g.eval_value(output)?.sum();
+            deriv.push((plus - minus) / two_h);
+        }
+        g.set_value(var, value.clone())?;
+        derivs.insert(var, from_vec(&value, deriv));
+    }
+    Ok(derivs)
+}
+
+/// Derivatives of the sum of the output elements by the complex step
+/// with respect to all variables
+///
+/// Returns `UnsupportedOperator` if the output depends on a custom operator,
+/// which cannot be evaluated with complex values.
+pub fn complex_step<A: Scalar<Real = A>>(
+    g: &Graph<A>,
+    output: NodeIndex,
+    step: A,
+) -> Result<HashMap<NodeIndex, Tensor<A>>> {
+    let order = g.topological_order(output);
+    let mut derivs = HashMap::new();
+    for var in g.variables() {
+        let value = g.get_value(var)?;
+        let mut deriv = Vec::with_capacity(value.len());
+        for i in 0..value.len() {
+            let y = eval_complex(g, &order, (var, i, A::complex(A::zero(), step)))?;
+            deriv.push(y.sum().im() / step);
+        }
+        derivs.insert(var, from_vec(&value, deriv));
+    }
+    Ok(derivs)
+}
+
+/// Compare `eval_deriv` with the central difference
+pub fn check_central<A: Scalar>(
+    g: &Graph<A>,
+    output: NodeIndex,
+    step: A::Real,
+) -> Result<Report<A>> {
+    let numerical = central_difference(g, output, step)?;
+    compare(g, output, &numerical)
+}
+
+/// Compare `eval_deriv` with the complex step
+pub fn check_complex_step<A: Scalar<Real = A>>(
+    g: &Graph<A>,
+    output: NodeIndex,
+    step: A,
+) -> Result<Report<A>> {
+    let numerical = complex_step(g, output, step)?;
+    compare(g, output, &numerical)
+}
+
+fn compare<A: Scalar>(
+    g: &Graph<A>,
+    output: NodeIndex,
+    numerical: &HashMap<NodeIndex, Tensor<A>>,
+) -> Result<Report<A>> {
+    let mut g = g.clone();
+    // derivatives of the previous passes must not be added
+    g.set_accumulate(false);
+    g.eval_value(output)?;
+    g.eval_deriv(output)?;
+    let mut vars: Vec<NodeIndex> = numerical.keys().cloned().collect();
+    vars.sort();
+    let variables = vars
+        .into_iter()
+        .map(|var| {
+            let analytic = match g.get_deriv(var) {
+                Ok(der) => der,
+                Err(_) => Tensor::zeros(numerical[&var].shape()),
+            };
+            let mut abs_error = A::real(0.0);
+            let mut rel_error = A::real(0.0);
+            for (a, n) in analytic.iter().zip(numerical[&var].iter()) {
+                let err = (*a - *n).abs();
+                let scale = max(a.abs(), n.abs());
+                if !(err.is_finite() && scale.is_finite()) {
+                    // NaN or infinity never passes the check
+                    abs_error = A::Real::infinity();
+                    rel_error = A::Real::infinity();
+                    continue;
+                }
+                abs_error = max(abs_error, err);
+                if scale > A::real(0.0) {
+                    rel_error = max(rel_error, err / scale);
+                }
+            }
+            let name = g
+                .namespace
+                .iter()
+                .find(|&(_, &n)| n == var)
+                .map(|(name, _)| name.clone());
+            VariableError {
+                index: var,
+                name,
+                abs_error,
+                rel_error,
+            }
+        })
+        .collect();
+    Ok(Report { variables })
+}
+
+/// Evaluate the ancestors of the output in the given topological order with complex values,
+/// where the `i`-th element of the variable `var` is shifted by `delta`.
+fn eval_complex<A: Scalar<Real = A>>(
+    g: &Graph<A>,
+    order: &[NodeIndex],
+    (var, i, delta): (NodeIndex, usize, A::Complex),
+) -> Result<Tensor<A::Complex>> {
+    let mut values: HashMap<NodeIndex, Tensor<A::Complex>> = HashMap::new();
+    for &idx in order {
+        let value = match g[idx].property {
+            Property::Variable | Property::Constant => {
+                let value = g.get_value(idx)?.mapv(|a| a.as_c()).into_shared();
+                if idx == var {
+                    perturbed(&value, i, delta)
+                } else {
+                    value
+                }
+            }
+            // `Square` takes `conj(a) * a`, which is not holomorphic.
+            // Its holomorphic extension from the real axis is used instead.
+            Property::Unary(Unary::Square) => {
+                let arg = values[&g.get_arg1(idx)].clone();
+                Binary::Mul.eval_value(arg.clone(), arg)
+            }
+            Property::Unary(op) => op.eval_value(values[&g.get_arg1(idx)].clone()),
+            Property::Binary(op) => {
+                let (lhs, rhs) = g.get_arg2(idx);
+                op.eval_value(values[&lhs].clone(), values[&rhs].clone())
+            }
+            Property::Custom(_) => {
+                return Err(Error::UnsupportedOperator { index: idx.index() });
+            }
+        };
+        values.insert(idx, value);
+    }
+    Ok(values.remove(&order[order.len() - 1]).unwrap())
+}
+
+/// Copy of the tensor whose `i`-th element in the logical order is shifted by `delta`
+fn perturbed<A: Scalar>(value: &Tensor<A>, i: usize, delta: A) -> Tensor<A> {
+    let mut value = value.to_owned();
+    *value.iter_mut().nth(i).unwrap() += delta;
+    value.into_shared()
+}
+
+/// Tensor of the same shape as `value` from the elements in the logical order
+fn from_vec<A: Scalar>(value: &Tensor<A>, elements: Vec<A>) -> Tensor<A> {
+    ArrayD::from_shape_vec(value.raw_dim(), elements)
+        .unwrap()
+        .into_shared()
+}
+
+fn max<R: PartialOrd>(a: R, b: R) -> R {
+    if a < b {
+        b
+    } else {
+        a
+    }
+}
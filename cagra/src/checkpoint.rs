@@ -0,0 +1,164 @@
+//! Gradient checkpointing
+//!
+//! The checkpointed evaluation keeps only the values of variables, constants,
+//! checkpoint nodes and the output, and drops the other intermediate values
+//! as soon as they are consumed. The backward sweep recomputes the dropped values
+//! from the nearest checkpoints segment by segment, and drops them again after use.
+//! Dropped values are regarded as dirty, and thus recomputed by `eval_value` as well.
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::graph::{Graph, Storage, Tensor};
+
+impl<A: Scalar> Graph<A> {
+    /// Mark the node as a checkpoint, whose value is kept in the checkpointed evaluation
+    pub fn mark_checkpoint(&mut self, node: NodeIndex) {
+        self[node].checkpoint = true;
+    }
+
+    /// Unmark all checkpoints
+    pub fn clear_checkpoints(&mut self) {
+        let nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        for node in nodes {
+            self[node].checkpoint = false;
+        }
+    }
+
+    /// Check the value of the node may be dropped
+    fn is_droppable(&self, node: NodeIndex) -> bool {
+        let n = &self[node];
+        !(n.checkpoint || n.is_variable() || n.is_constant())
+    }
+
+    /// Evaluate the dirty ancestors of the node, dropping the droppable values
+    /// once all of their consumers are evaluated.
+    /// Returns the sizes of the evaluated values.
+    fn forward_dropping(&mut self, node: NodeIndex) -> Result<HashMap<NodeIndex, usize>> {
+        let dirty = self.topological_order_filtered(node, |n| self[n].value.is_none());
+        let mut uses: HashMap<NodeIndex, usize> = HashMap::new();
+        for &idx in &dirty {
+            for arg in self.args(idx) {
+                *uses.entry(arg).or_insert(0) += 1;
+            }
+        }
+        let mut sizes = HashMap::new();
+        for &idx in &dirty {
+            let value = self.eval_node(idx, |n| self.get_value(n))?;
+            sizes.insert(idx, value.len());
+            self[idx].value = Some(value);
+            for arg in self.args(idx) {
+                let remaining = uses.get_mut(&arg).unwrap();
+                *remaining -= 1;
+                if *remaining == 0
+                    && arg != node
+                    && sizes.contains_key(&arg)
+                    && self.is_droppable(arg)
+                {
+                    self[arg].value = None;
+                }
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// Evaluate the value of the node keeping only the values of variables, constants,
+    /// checkpoints and the node itself.
+    ///
+    /// The values of the other ancestors cached by earlier evaluations are dropped as well.
+    pub fn eval_value_checkpointed(&mut self, node: NodeIndex) -> Result<Tensor<A>> {
+        self.forward_dropping(node)?;
+        for idx in self.topological_order(node) {
+            if idx != node && self.is_droppable(idx) {
+                self[idx].value = None;
+            }
+        }
+        self.get_value(node)
+    }
+
+    /// Same as `eval_deriv`, but the intermediate values are dropped in the forward sweep,
+    /// and recomputed from the checkpoints in the backward sweep.
+    pub fn eval_deriv_checkpointed(&mut self, node: NodeIndex) -> Result<()> {
+        let value = self.eval_value_checkpointed(node)?;
+        self.with_cleared_derivs(|g| g.backward_checkpointed(node, value))
+    }
+
+    fn backward_checkpointed(&mut self, node: NodeIndex, value: Tensor<A>) -> Result<()> {
+        self[node].deriv = Some(Tensor::ones(value.shape()));
+        for idx in self.topological_order(node).into_iter().rev() {
+            if let Some(der) = self[idx].deriv.clone() {
+                for arg in self.args(idx) {
+                    if self[arg].value.is_none() {
+                        // recompute the segment from the checkpoints
+                        self.eval_value(arg)?;
+                    }
+                }
+                for (arg, der) in self.deriv_node(idx, der, |n| self.get_value(n))? {
+                    self.accumulate_deriv(arg, der);
+                }
+            }
+            // consumers of the node are already processed
+            if idx != node && self.is_droppable(idx) {
+                self[idx].value = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose checkpoints among the ancestors of the output, and returns them.
+    ///
+    /// The checkpoints are placed at even intervals of the accumulated size of
+    /// the intermediate values in topological order, so that their total number of
+    /// elements does not exceed `budget`. If all intermediate values fit in the budget,
+    /// all of them are checkpoints. The previous checkpoints of the ancestors are unmarked.
+    /// The output is evaluated twice by `eval_value_checkpointed`, first to measure
+    /// the sizes of the intermediate values, and then to store the values of the checkpoints.
+    pub fn choose_checkpoints(
+        &mut self,
+        output: NodeIndex,
+        budget: usize,
+    ) -> Result<Vec<NodeIndex>> {
+        let order = self.topological_order(output);
+        // re-evaluate all intermediate values to measure their sizes
+        for &node in &order {
+            self[node].checkpoint = false;
+            if self.is_droppable(node) {
+                self[node].value = None;
+            }
+        }
+        let mut sizes = self.forward_dropping(output)?;
+        let intermediates: Vec<(NodeIndex, usize)> = order
+            .into_iter()
+            .filter(|&n| n != output && self.is_droppable(n))
+            .map(|n| (n, sizes.remove(&n).unwrap()))
+            .collect();
+        let total: usize = intermediates.iter().map(|(_, size)| size).sum();
+        // expected number of checkpoints of the average size, and the interval between them
+        let interval = if total <= budget {
+            0
+        } else {
+            total / (budget * intermediates.len() / total + 1)
+        };
+
+        let mut checkpoints = Vec::new();
+        let mut used = 0;
+        let mut since = 0;
+        for (node, size) in intermediates {
+            since += size;
+            if since >= interval && used + size <= budget {
+                self[node].checkpoint = true;
+                checkpoints.push(node);
+                used += size;
+                since = 0;
+            }
+        }
+        // fill the values of the new checkpoints
+        if self.is_droppable(output) {
+            self[output].value = None;
+        }
+        self.forward_dropping(output)?;
+        Ok(checkpoints)
+    }
+}
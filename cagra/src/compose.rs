@@ -0,0 +1,91 @@
+//! Composition of graphs
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Property};
+
+impl<A: Scalar> Graph<A> {
+    /// Copy the nodes of `other` into this graph, and returns the map from the names
+    /// in `other` to the copied nodes.
+    ///
+    /// The variables of `other` named in `input_map` are not copied, and their consumers
+    /// are connected to the given nodes of this graph instead.
+    /// The other variables are copied with their values.
+    /// The names of the copied nodes are registered in this graph with `prefix`,
+    /// e.g. `x` in `other` is registered as `pot.x` for the prefix `pot.`,
+    /// which keeps the names of several inlined copies of a graph from clashing.
+    /// The returned map is keyed by the names in `other` without the prefix.
+    ///
+    /// This fails without changing the graph if a name in `input_map` is not a variable
+    /// of `other` (`UndefinedName` or `NodeTypeError`), a node in `input_map` does not exist
+    /// in this graph (`NodeNotFound`), or a prefixed name is already used in this graph
+    /// (`DuplicatedName`).
+    pub fn inline(
+        &mut self,
+        other: &Graph<A>,
+        prefix: &str,
+        input_map: &[(&str, NodeIndex)],
+    ) -> Result<HashMap<String, NodeIndex>> {
+        let mut remap: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        for &(name, node) in input_map {
+            if !self.graph.contains_node(node) {
+                return Err(Error::NodeNotFound {
+                    index: node.index(),
+                });
+            }
+            let var = match other.namespace.get(name) {
+                Some(&var) => var,
+                None => return Err(Error::UndefinedName { name: name.into() }),
+            };
+            if !other[var].is_variable() {
+                return Err(Error::NodeTypeError { index: var.index() });
+            }
+            remap.insert(var, node);
+        }
+        let inputs: Vec<NodeIndex> = remap.keys().cloned().collect();
+        for (name, var) in &other.namespace {
+            let name = format!("{}{}", prefix, name);
+            if !inputs.contains(var) && self.namespace.contains_key(&name) {
+                return Err(Error::DuplicatedName { name });
+            }
+        }
+
+        for node in other.toposort() {
+            if remap.contains_key(&node) {
+                continue;
+            }
+            let mut copied = other[node].clone();
+            copied.deriv = None;
+            match copied.property {
+                Property::Variable | Property::Constant => {}
+                Property::Unary(_) | Property::Binary(_) => {
+                    // inputs may be replaced
+                    copied.value = None;
+                }
+                Property::Custom(id) => {
+                    self.operators.push(other.operators[id].clone());
+                    copied.property = Property::Custom(self.operators.len() - 1);
+                    copied.value = None;
+                }
+            }
+            let new = self.graph.add_node(copied);
+            for arg in other.args(node) {
+                self.graph.add_edge(remap[&arg], new, ());
+            }
+            remap.insert(node, new);
+        }
+
+        let mut handles = HashMap::new();
+        for (name, var) in &other.namespace {
+            let new = remap[var];
+            if !inputs.contains(var) {
+                self.namespace.insert(format!("{}{}", prefix, name), new);
+            }
+            handles.insert(name.clone(), new);
+        }
+        Ok(handles)
+    }
+}
@@ -1,3 +1,6 @@
+// `failure_derive` expands into impls nested in an anonymous const
+#![allow(non_local_definitions)]
+
 use failure::Fail;
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -15,6 +18,45 @@ pub enum Error {
     #[fail(display = "Node type mismatch (Index = {})", index)]
     NodeTypeError { index: usize },
 
+    /// node does not exist in the graph
+    #[fail(display = "Node does not exist (Index = {})", index)]
+    NodeNotFound { index: usize },
+
+    /// node is not compiled into the tape
+    #[fail(display = "Node is not compiled into the tape (Index = {})", index)]
+    NodeNotCompiled { index: usize },
+
+    /// value slot of the tape is reused by later instructions
+    #[fail(display = "Value is released on the tape (Index = {})", index)]
+    ValueReleased { index: usize },
+
+    /// node is not an operand of the operator
+    #[fail(
+        display = "Node is not an operand (Index = {}, Operand = {})",
+        index, operand
+    )]
+    OperandNotFound { index: usize, operand: usize },
+
+    /// node is still used by other nodes
+    #[fail(display = "Node is used by other nodes (Index = {})", index)]
+    NodeInUse { index: usize },
+
+    /// edit makes the graph cyclic
+    #[fail(display = "Graph becomes cyclic (Index = {})", index)]
+    CyclicGraph { index: usize },
+
+    /// operator does not support the evaluation
+    #[fail(display = "Operator is not supported (Index = {})", index)]
+    UnsupportedOperator { index: usize },
+
+    /// custom operator is not registered
+    #[fail(display = "Operator is not registered (name = {})", name)]
+    UnregisteredOperator { name: String },
+
+    /// Wildcard used in a replacement is not bound by the pattern
+    #[fail(display = "Wildcard is not bound by the pattern (id = {})", id)]
+    UnboundWildcard { id: usize },
+
     /// Name duplication in a graph
     #[fail(display = "Duplicated name (name = {})", name)]
     DuplicatedName { name: String },
@@ -27,10 +69,32 @@ pub enum Error {
     #[fail(display = "JSON serialization failed: {:?})", error)]
     JSONSerializeFailed { error: serde_json::error::Error },
 
+    /// Fail to deserialize from JSON
+    #[fail(display = "JSON deserialization failed: {:?})", error)]
+    JSONDeserializeFailed { error: serde_json::error::Error },
+
+    /// Deserialized node refers to a missing node, or has wrong number of arguments
+    #[fail(display = "Malformed node in JSON (position = {})", position)]
+    MalformedNode { position: usize },
+
+    /// Deserialized name refers to a missing node
+    #[fail(display = "Malformed name in JSON (name = {})", name)]
+    MalformedName { name: String },
+
     /// Tensor rank mismatch
     #[fail(
         display = "Tensor rank is mismatched: actual={}, desired={}",
         actual, desired
     )]
     TensorRankMismatch { actual: usize, desired: usize },
+
+    /// Tensor shape mismatch
+    #[fail(
+        display = "Tensor shape is mismatched: actual={:?}, desired={:?}",
+        actual, desired
+    )]
+    TensorShapeMismatch {
+        actual: Vec<usize>,
+        desired: Vec<usize>,
+    },
 }
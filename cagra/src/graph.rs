@@ -1,13 +1,18 @@
 //! Calculation graph
 
 use petgraph::prelude::*;
+use petgraph::stable_graph::StableGraph;
+use petgraph::visit::{VisitMap, Visitable};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::sync::Arc;
 use std::{fmt, io};
 
 use super::error::{Error, Result};
-use super::operator::{Binary, Unary};
+use super::operator::{Binary, Operator, Unary};
+use super::tensor::{check_shape, unit_tensor};
 use cauchy::Scalar;
+use ndarray::Array2;
 
 pub type Tensor<A> = ndarray::ArcArray<A, ndarray::IxDyn>;
 
@@ -30,17 +35,20 @@ macro_rules! graph {
 /// using this value.
 #[derive(Clone)]
 pub struct Node<A: Scalar> {
-    value: Option<Tensor<A>>,
-    deriv: Option<Tensor<A>>,
-    property: Property,
+    pub(crate) value: Option<Tensor<A>>,
+    pub(crate) deriv: Option<Tensor<A>>,
+    pub(crate) property: Property,
+    /// Keep the value in the checkpointed evaluation
+    pub(crate) checkpoint: bool,
 }
 
 impl<A: Scalar + fmt::Debug> fmt::Debug for Node<A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.property {
             Property::Constant | Property::Variable => {}
-            Property::Unary(unary) => write!(f, "Unary: {:?}\n", unary)?,
-            Property::Binary(bin) => write!(f, "Binary: {:?}\n", bin)?,
+            Property::Unary(unary) => writeln!(f, "Unary: {:?}", unary)?,
+            Property::Binary(bin) => writeln!(f, "Binary: {:?}", bin)?,
+            Property::Custom(id) => writeln!(f, "Custom: #{}", id)?,
         }
         if let Some(val) = &self.value {
             write!(f, "value={:?}", val)?
@@ -58,12 +66,14 @@ impl<A: Scalar + fmt::Debug> fmt::Debug for Node<A> {
 }
 
 /// Extra propaties of the `Node` accoding to the node type.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
-enum Property {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum Property {
     Constant,
     Variable,
     Unary(Unary),
     Binary(Binary),
+    /// Index of the custom operator in `Graph::operators`
+    Custom(usize),
 }
 
 impl<A: Scalar> Node<A> {
@@ -74,6 +84,18 @@ impl<A: Scalar> Node<A> {
             Property::Variable => true,
             Property::Unary(_) => false,
             Property::Binary(_) => false,
+            Property::Custom(_) => false,
+        }
+    }
+
+    /// Check the node is constant
+    pub fn is_constant(&self) -> bool {
+        match self.property {
+            Property::Constant => true,
+            Property::Variable => false,
+            Property::Unary(_) => false,
+            Property::Binary(_) => false,
+            Property::Custom(_) => false,
         }
     }
 
@@ -81,6 +103,7 @@ impl<A: Scalar> Node<A> {
         Self {
             value: None,
             deriv: None,
+            checkpoint: false,
             property: Property::Variable,
         }
     }
@@ -89,6 +112,7 @@ impl<A: Scalar> Node<A> {
         Self {
             value: Some(a),
             deriv: None,
+            checkpoint: false,
             property: Property::Constant,
         }
     }
@@ -99,6 +123,7 @@ impl<A: Scalar> From<Unary> for Node<A> {
         Self {
             value: None,
             deriv: None,
+            checkpoint: false,
             property: Property::Unary(op),
         }
     }
@@ -109,16 +134,21 @@ impl<A: Scalar> From<Binary> for Node<A> {
         Self {
             value: None,
             deriv: None,
+            checkpoint: false,
             property: Property::Binary(op),
         }
     }
 }
 
-/// Calculation graph based on `petgraph::graph::Graph`
+/// Calculation graph based on `petgraph::stable_graph::StableGraph`
+///
+/// Indices of nodes are kept valid when other nodes are removed.
 #[derive(Debug, Clone)]
 pub struct Graph<A: Scalar> {
-    graph: petgraph::graph::Graph<Node<A>, ()>,
-    namespace: HashMap<String, NodeIndex>,
+    pub(crate) graph: StableGraph<Node<A>, ()>,
+    pub(crate) namespace: HashMap<String, NodeIndex>,
+    pub(crate) operators: Vec<Arc<dyn Operator<A>>>,
+    pub(crate) accumulate: bool,
 }
 
 impl<A: Scalar> Graph<A> {
@@ -127,6 +157,138 @@ impl<A: Scalar> Graph<A> {
     }
 }
 
+impl<A: Scalar> Default for Graph<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Values and derivatives of the nodes used in the reverse sweep
+///
+/// They are kept in the nodes of `Graph` itself, or separately from the graph
+/// as in `Session`. The sweep is implemented once on top of this trait.
+pub(crate) trait Storage<A: Scalar> {
+    /// Structure of the graph
+    fn graph(&self) -> &Graph<A>;
+
+    fn value(&self, node: NodeIndex) -> Result<Tensor<A>>;
+
+    fn deriv_mut(&mut self, node: NodeIndex) -> &mut Option<Tensor<A>>;
+
+    /// Whether the derivatives of the previous passes are kept, see `Graph::set_accumulate`
+    fn is_accumulating(&self) -> bool;
+
+    fn accumulate_deriv(&mut self, node: NodeIndex, der: Tensor<A>) {
+        let slot = self.deriv_mut(node);
+        *slot = match slot.take() {
+            Some(der_last) => Some(der_last + der),
+            None => Some(der),
+        };
+    }
+
+    /// Run a backward sweep starting from cleared derivatives. In the accumulation mode,
+    /// the derivatives of the previous passes are added back after the sweep,
+    /// so that they are not propagated again.
+    fn with_cleared_derivs<F>(&mut self, sweep: F) -> Result<()>
+    where
+        Self: Sized,
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let nodes: Vec<NodeIndex> = self.graph().graph.node_indices().collect();
+        let last: Vec<_> = nodes
+            .into_iter()
+            .filter_map(|node| self.deriv_mut(node).take().map(|der| (node, der)))
+            .collect();
+        let result = sweep(self);
+        if self.is_accumulating() {
+            for (node, der) in last {
+                self.accumulate_deriv(node, der);
+            }
+        }
+        result
+    }
+
+    /// Reverse-mode accumulation starting from the nodes with the given derivatives.
+    ///
+    /// Nodes are visited in reverse topological order, so the derivatives from all
+    /// consumers of a node are summed up before being propagated to its arguments,
+    /// and each node is visited only once.
+    fn backward(&mut self, seeds: Vec<(NodeIndex, Tensor<A>)>) -> Result<()> {
+        let nodes: Vec<NodeIndex> = seeds.iter().map(|(node, _)| *node).collect();
+        for (node, der) in seeds {
+            self.accumulate_deriv(node, der);
+        }
+        let order = self.graph().topological_order_many(&nodes);
+        for idx in order.into_iter().rev() {
+            let der = match self.deriv_mut(idx).clone() {
+                Some(der) => der,
+                None => continue,
+            };
+            let ders = self.graph().deriv_node(idx, der, |n| self.value(n))?;
+            for (arg, der) in ders {
+                self.accumulate_deriv(arg, der);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<A: Scalar> Storage<A> for Graph<A> {
+    fn graph(&self) -> &Graph<A> {
+        self
+    }
+
+    fn value(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        self.get_value(node)
+    }
+
+    fn deriv_mut(&mut self, node: NodeIndex) -> &mut Option<Tensor<A>> {
+        &mut self[node].deriv
+    }
+
+    fn is_accumulating(&self) -> bool {
+        self.accumulate
+    }
+}
+
+/// Derivatives of an internal sweep kept apart from `Node::deriv`,
+/// which never accumulate over sweeps
+pub(crate) struct Scratch<'a, A: Scalar> {
+    graph: &'a Graph<A>,
+    derivs: HashMap<NodeIndex, Option<Tensor<A>>>,
+}
+
+impl<'a, A: Scalar> Scratch<'a, A> {
+    pub(crate) fn new(graph: &'a Graph<A>) -> Self {
+        Scratch {
+            graph,
+            derivs: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get_deriv(&self, node: NodeIndex) -> Option<&Tensor<A>> {
+        self.derivs.get(&node).and_then(Option::as_ref)
+    }
+}
+
+impl<'a, A: Scalar> Storage<A> for Scratch<'a, A> {
+    fn graph(&self) -> &Graph<A> {
+        self.graph
+    }
+
+    fn value(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        self.graph.get_value(node)
+    }
+
+    fn deriv_mut(&mut self, node: NodeIndex) -> &mut Option<Tensor<A>> {
+        self.derivs.entry(node).or_insert(None)
+    }
+
+    fn is_accumulating(&self) -> bool {
+        false
+    }
+}
+
 // Panic if the index does not exists
 impl<A: Scalar> ::std::ops::Index<NodeIndex> for Graph<A> {
     type Output = Node<A>;
@@ -191,17 +353,35 @@ impl<A: Scalar> Graph<A> {
     def_unary!(sinh, Sinh);
     def_unary!(cosh, Cosh);
     def_unary!(tanh, Tanh);
+    def_unary!(stop_gradient, StopGradient);
 
     pub fn sub(&mut self, lhs: NodeIndex, rhs: NodeIndex) -> NodeIndex {
         let m_rhs = self.neg(rhs);
         self.add(lhs, m_rhs)
     }
 
+    /// Append the custom operator applied to the arguments
+    pub fn custom(&mut self, op: Box<dyn Operator<A>>, args: &[NodeIndex]) -> NodeIndex {
+        self.operators.push(Arc::from(op));
+        let n = self.graph.add_node(Node {
+            value: None,
+            deriv: None,
+            checkpoint: false,
+            property: Property::Custom(self.operators.len() - 1),
+        });
+        for &arg in args {
+            self.graph.add_edge(arg, n, ());
+        }
+        n
+    }
+
     /// new graph.
     pub fn new() -> Self {
         Self {
-            graph: petgraph::graph::Graph::new(),
+            graph: StableGraph::new(),
             namespace: HashMap::new(),
+            operators: Vec::new(),
+            accumulate: false,
         }
     }
 
@@ -255,14 +435,26 @@ impl<A: Scalar> Graph<A> {
         Ok(var)
     }
 
+    /// Indices of the variable nodes
+    pub fn variables(&self) -> Vec<NodeIndex> {
+        self.graph
+            .node_indices()
+            .filter(|&n| self[n].is_variable())
+            .collect()
+    }
+
     pub fn set_name(&mut self, node: NodeIndex, name: &str) -> Option<NodeIndex> {
         self.namespace.insert(name.to_string(), node)
     }
 
     /// Set a value to a variable node, and returns `NodeTypeError` if the node is an operator.
+    ///
+    /// The cached values of all nodes depending on the variable are discarded,
+    /// and they will be recomputed in the next `eval_value`.
     pub fn set_value(&mut self, node: NodeIndex, value: Tensor<A>) -> Result<()> {
         if self.graph[node].is_variable() {
             self.graph[node].value = Some(value);
+            self.invalidate(node);
             Ok(())
         } else {
             Err(Error::NodeTypeError {
@@ -271,42 +463,278 @@ impl<A: Scalar> Graph<A> {
         }
     }
 
-    fn get_arg1(&mut self, op: NodeIndex) -> NodeIndex {
+    pub(crate) fn get_arg1(&self, op: NodeIndex) -> NodeIndex {
         let mut iter = self.graph.neighbors_directed(op, Direction::Incoming);
         iter.next().unwrap()
     }
 
-    fn get_arg2(&mut self, op: NodeIndex) -> (NodeIndex, NodeIndex) {
+    pub(crate) fn get_arg2(&self, op: NodeIndex) -> (NodeIndex, NodeIndex) {
         let mut iter = self.graph.neighbors_directed(op, Direction::Incoming);
         let rhs = iter.next().unwrap();
         let lhs = iter.next().unwrap();
         (lhs, rhs)
     }
 
-    /// Evaluate the value of the node recusively.
-    pub fn eval_value(&mut self, node: NodeIndex) -> Result<Tensor<A>> {
-        let prop = self[node].property;
-        match prop {
-            Property::Variable => self.get_value(node),
-            Property::Constant => Ok(self
-                .get_value(node)
-                .expect("Constant node is not initialized")),
-            Property::Unary(ref op) => Ok(self.get_value(node).unwrap_or({
+    /// Arguments of the operator in the order of lhs, rhs
+    pub(crate) fn args(&self, op: NodeIndex) -> Vec<NodeIndex> {
+        let mut args: Vec<NodeIndex> = self
+            .graph
+            .neighbors_directed(op, Direction::Incoming)
+            .collect();
+        args.reverse();
+        args
+    }
+
+    /// Replace all arguments of the operator, given in the order of lhs, rhs
+    pub(crate) fn set_args(&mut self, op: NodeIndex, args: &[NodeIndex]) {
+        let edges: Vec<EdgeIndex> = self
+            .graph
+            .edges_directed(op, Direction::Incoming)
+            .map(|e| e.id())
+            .collect();
+        for edge in edges {
+            self.graph.remove_edge(edge);
+        }
+        for &arg in args {
+            self.graph.add_edge(arg, op, ());
+        }
+    }
+
+    /// Replace `old` by `new` in the arguments of the consumers of `old` and in the namespace
+    pub(crate) fn redirect(&mut self, old: NodeIndex, new: NodeIndex) {
+        let mut consumers: Vec<NodeIndex> = self
+            .graph
+            .neighbors_directed(old, Direction::Outgoing)
+            .collect();
+        consumers.sort();
+        consumers.dedup();
+        for consumer in consumers {
+            let args: Vec<NodeIndex> = self
+                .args(consumer)
+                .into_iter()
+                .map(|arg| if arg == old { new } else { arg })
+                .collect();
+            self.set_args(consumer, &args);
+        }
+        for index in self.namespace.values_mut() {
+            if *index == old {
+                *index = new;
+            }
+        }
+    }
+
+    /// Replace the operand `old` of the operator `node` by `new`.
+    ///
+    /// Every occurrence of `old` in the operands is replaced, e.g. both of `mul(x, x)`.
+    /// The cached values of `node` and the nodes depending on it are discarded.
+    pub fn replace_operand(
+        &mut self,
+        node: NodeIndex,
+        old: NodeIndex,
+        new: NodeIndex,
+    ) -> Result<()> {
+        let args = self.args(node);
+        if !args.contains(&old) {
+            return Err(Error::OperandNotFound {
+                index: node.index(),
+                operand: old.index(),
+            });
+        }
+        if new == node || self.descendants(node).contains(&new) {
+            return Err(Error::CyclicGraph { index: new.index() });
+        }
+        let args: Vec<NodeIndex> = args
+            .into_iter()
+            .map(|arg| if arg == old { new } else { arg })
+            .collect();
+        self.set_args(node, &args);
+        self[node].value = None;
+        self.invalidate(node);
+        Ok(())
+    }
+
+    /// Replace `old` by `new` in the operands of all consumers of `old`,
+    /// and redirect the names of `old` to `new`.
+    ///
+    /// `old` itself is left in the graph. `new` must not depend on `old`.
+    /// The cached values of the nodes depending on `new` are discarded.
+    pub fn replace_node(&mut self, old: NodeIndex, new: NodeIndex) -> Result<()> {
+        if old == new {
+            return Ok(());
+        }
+        if self.descendants(old).contains(&new) {
+            return Err(Error::CyclicGraph { index: new.index() });
+        }
+        self.redirect(old, new);
+        self.invalidate(new);
+        Ok(())
+    }
+
+    /// Remove the node, which must not be used by other nodes, and its names.
+    ///
+    /// Indices of the other nodes are kept valid.
+    pub fn remove_node(&mut self, node: NodeIndex) -> Result<Node<A>> {
+        if self
+            .graph
+            .neighbors_directed(node, Direction::Outgoing)
+            .next()
+            .is_some()
+        {
+            return Err(Error::NodeInUse {
+                index: node.index(),
+            });
+        }
+        self.namespace.retain(|_, n| *n != node);
+        Ok(self.graph.remove_node(node).unwrap())
+    }
+
+    /// Mark every node depending on the node dirty by discarding its cached value
+    fn invalidate(&mut self, node: NodeIndex) {
+        for n in self.descendants(node) {
+            self[n].value = None;
+        }
+    }
+
+    /// Nodes depending on the node (excluding itself)
+    pub(crate) fn descendants(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut visited = self.graph.visit_map();
+        let mut descendants = Vec::new();
+        let mut stack: Vec<_> = self
+            .graph
+            .neighbors_directed(node, Direction::Outgoing)
+            .collect();
+        while let Some(n) = stack.pop() {
+            if !visited.visit(n) {
+                continue;
+            }
+            descendants.push(n);
+            stack.extend(self.graph.neighbors_directed(n, Direction::Outgoing));
+        }
+        descendants
+    }
+
+    /// Ancestors of the node (including itself) in topological order,
+    /// i.e. every node appears after all of its arguments.
+    ///
+    /// This uses an explicit stack instead of recursion,
+    /// and thus the depth of the graph is limited only by memory.
+    pub(crate) fn topological_order(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        self.topological_sort(&[node], |_| true)
+    }
+
+    /// Ancestors of any of the nodes (including themselves) in topological order
+    pub(crate) fn topological_order_many(&self, nodes: &[NodeIndex]) -> Vec<NodeIndex> {
+        self.topological_sort(nodes, |_| true)
+    }
+
+    /// Same as `topological_order`, but nodes for which `filter` returns `false`
+    /// are skipped together with their ancestors reachable only through them.
+    pub(crate) fn topological_order_filtered<F>(&self, node: NodeIndex, filter: F) -> Vec<NodeIndex>
+    where
+        F: Fn(NodeIndex) -> bool,
+    {
+        self.topological_sort(&[node], filter)
+    }
+
+    fn topological_sort<F>(&self, nodes: &[NodeIndex], filter: F) -> Vec<NodeIndex>
+    where
+        F: Fn(NodeIndex) -> bool,
+    {
+        let mut visited = self.graph.visit_map();
+        let mut order = Vec::new();
+        let mut stack: Vec<_> = nodes.iter().rev().map(|&n| (n, false)).collect();
+        while let Some((n, expanded)) = stack.pop() {
+            if expanded {
+                order.push(n);
+                continue;
+            }
+            if !visited.visit(n) || !filter(n) {
+                continue;
+            }
+            stack.push((n, true));
+            for arg in self.graph.neighbors_directed(n, Direction::Incoming) {
+                if !visited.is_visited(&arg) {
+                    stack.push((arg, false));
+                }
+            }
+        }
+        order
+    }
+
+    /// Evaluate the value of the node from the values of its arguments.
+    /// Variables and constants return their own value given by `value`.
+    pub(crate) fn eval_node<F>(&self, node: NodeIndex, value: F) -> Result<Tensor<A>>
+    where
+        F: Fn(NodeIndex) -> Result<Tensor<A>>,
+    {
+        match self[node].property {
+            Property::Variable | Property::Constant => value(node),
+            Property::Unary(op) => {
+                let arg = self.get_arg1(node);
+                Ok(op.eval_value(value(arg)?))
+            }
+            Property::Binary(op) => {
+                let (lhs, rhs) = self.get_arg2(node);
+                Ok(op.eval_value(value(lhs)?, value(rhs)?))
+            }
+            Property::Custom(id) => {
+                let args = self
+                    .args(node)
+                    .into_iter()
+                    .map(value)
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(self.operators[id].eval_value(&args))
+            }
+        }
+    }
+
+    /// Derivatives propagated from the node to its arguments,
+    /// i.e. the derivative of the node multiplied by the derivative of the operator.
+    pub(crate) fn deriv_node<F>(
+        &self,
+        node: NodeIndex,
+        der: Tensor<A>,
+        value: F,
+    ) -> Result<Vec<(NodeIndex, Tensor<A>)>>
+    where
+        F: Fn(NodeIndex) -> Result<Tensor<A>>,
+    {
+        Ok(match self[node].property {
+            Property::Variable | Property::Constant => Vec::new(),
+            Property::Unary(Unary::StopGradient) => Vec::new(),
+            Property::Unary(op) => {
                 let arg = self.get_arg1(node);
-                let val1 = self.eval_value(arg)?;
-                let value = op.eval_value(val1);
-                self[node].value = Some(value.clone()); // cache
-                value
-            })),
-            Property::Binary(ref op) => Ok(self.get_value(node).unwrap_or({
+                vec![(arg, op.eval_deriv(value(arg)?, der))]
+            }
+            Property::Binary(op) => {
                 let (lhs, rhs) = self.get_arg2(node);
-                let lv = self.eval_value(lhs)?;
-                let rv = self.eval_value(rhs)?;
-                let value = op.eval_value(lv, rv);
-                self[node].value = Some(value.clone()); // cache
-                value
-            })),
+                let (l_der, r_der) = op.eval_deriv(value(lhs)?, value(rhs)?, der);
+                vec![(lhs, l_der), (rhs, r_der)]
+            }
+            Property::Custom(id) => {
+                let args = self.args(node);
+                let values = args
+                    .iter()
+                    .map(|&arg| value(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let ders = self.operators[id].eval_deriv(&values, der);
+                args.into_iter().zip(ders).collect()
+            }
+        })
+    }
+
+    /// Evaluate the value of the node.
+    ///
+    /// Only the dirty ancestors of the node, i.e. the nodes whose cached value has been
+    /// discarded by `set_value`, are recomputed in topological order.
+    /// Clean cached values are reused.
+    pub fn eval_value(&mut self, node: NodeIndex) -> Result<Tensor<A>> {
+        let dirty = self.topological_order_filtered(node, |n| self[n].value.is_none());
+        for idx in dirty {
+            let value = self.eval_node(idx, |n| self.get_value(n))?;
+            self[idx].value = Some(value); // cache
         }
+        self.get_value(node)
     }
 
     pub fn get_value(&self, node: NodeIndex) -> Result<Tensor<A>> {
@@ -321,37 +749,257 @@ impl<A: Scalar> Graph<A> {
         })
     }
 
-    fn deriv_recur(&mut self, node: NodeIndex, der: Tensor<A>) -> Result<()> {
-        self[node].deriv = match self[node].deriv.clone() {
-            Some(der_last) => Some(der_last + der.clone()),
-            None => Some(der.clone()),
-        };
-        let property = self[node].property;
-        match property {
-            Property::Variable | Property::Constant => {}
-            Property::Unary(ref op) => {
-                let arg = self.get_arg1(node);
-                let der = op.eval_deriv(self.get_value(arg)?, der);
-                self.deriv_recur(arg, der)?;
-            }
-            Property::Binary(ref op) => {
-                let (lhs, rhs) = self.get_arg2(node);
-                let (l_der, r_der) = op.eval_deriv(self.get_value(lhs)?, self.get_value(rhs)?, der);
-                self.deriv_recur(lhs, l_der)?;
-                self.deriv_recur(rhs, r_der)?;
-            }
-        };
-        Ok(())
+    /// Discard the derivatives of all nodes
+    pub fn zero_grad(&mut self) {
+        let nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        for node in nodes {
+            self[node].deriv = None;
+        }
+    }
+
+    /// Switch the accumulation mode of the backward passes (disabled by default).
+    ///
+    /// When enabled, the derivatives of a backward pass are added into the ones of
+    /// the previous passes instead of replacing them, until `zero_grad` is called.
+    pub fn set_accumulate(&mut self, accumulate: bool) {
+        self.accumulate = accumulate;
+    }
+
+    pub fn is_accumulating(&self) -> bool {
+        self.accumulate
     }
 
-    /// Evaluate derivative recursively.
+    /// Evaluate derivative of the node with respect to its ancestors.
     pub fn eval_deriv(&mut self, node: NodeIndex) -> Result<()> {
-        for idx in self.graph.node_indices() {
-            self[idx].deriv = None;
+        let one = Tensor::ones(self.get_value(node)?.shape());
+        self.eval_deriv_with_seed(node, one)
+    }
+
+    /// Evaluate derivative of the node with respect to its ancestors,
+    /// seeded by the given derivative of the node (vector-Jacobian product).
+    ///
+    /// Returns `TensorShapeMismatch` if the shape of the seed differs from the value.
+    pub fn eval_deriv_with_seed(&mut self, node: NodeIndex, seed: Tensor<A>) -> Result<()> {
+        check_shape(&seed, self.get_value(node)?.shape())?;
+        self.with_cleared_derivs(|g| g.backward(vec![(node, seed)]))
+    }
+
+    /// Evaluate derivative of the sum of the outputs weighted by the seeds,
+    /// i.e. the sum of the vector-Jacobian products, in a single reverse sweep.
+    ///
+    /// An output may be an ancestor of another one, and may appear more than once.
+    /// Returns `TensorShapeMismatch` if the shape of a seed differs from its output.
+    pub fn eval_deriv_many(&mut self, seeds: &[(NodeIndex, Tensor<A>)]) -> Result<()> {
+        for (node, seed) in seeds {
+            check_shape(seed, self.get_value(*node)?.shape())?;
+        }
+        self.with_cleared_derivs(|g| g.backward(seeds.to_vec()))
+    }
+
+    /// Evaluate derivative of the node with respect to the given variables,
+    /// and returns the map from each variable to its derivative.
+    ///
+    /// Only the nodes on the paths from the variables to the node are visited,
+    /// and `Node::deriv` of the graph is left untouched.
+    /// The derivative is zero for a variable which the node does not depend on.
+    /// Returns `NodeTypeError` if one of `vars` is not a variable.
+    pub fn eval_deriv_wrt(
+        &self,
+        node: NodeIndex,
+        vars: &[NodeIndex],
+    ) -> Result<HashMap<NodeIndex, Tensor<A>>> {
+        let mut on_path = HashSet::new();
+        for &var in vars {
+            if !self[var].is_variable() {
+                return Err(Error::NodeTypeError { index: var.index() });
+            }
+            on_path.insert(var);
+            on_path.extend(self.descendants(var));
+        }
+        let mut derivs: HashMap<NodeIndex, Tensor<A>> = HashMap::new();
+        if on_path.contains(&node) {
+            derivs.insert(node, Tensor::ones(self.get_value(node)?.shape()));
+        }
+        let order = self.topological_order_filtered(node, |n| on_path.contains(&n));
+        for idx in order.into_iter().rev() {
+            if self[idx].is_variable() {
+                continue;
+            }
+            // drop the derivative of the intermediate node once propagated
+            let der = match derivs.remove(&idx) {
+                Some(der) => der,
+                None => continue,
+            };
+            for (arg, der) in self.deriv_node(idx, der, |n| self.get_value(n))? {
+                if !on_path.contains(&arg) {
+                    continue;
+                }
+                let der = match derivs.remove(&arg) {
+                    Some(der_last) => der_last + der,
+                    None => der,
+                };
+                derivs.insert(arg, der);
+            }
+        }
+        vars.iter()
+            .map(|&var| {
+                let der = match derivs.get(&var) {
+                    Some(der) => der.clone(),
+                    None => Tensor::zeros(self.get_value(var)?.shape()),
+                };
+                Ok((var, der))
+            })
+            .collect()
+    }
+
+    /// Evaluate the directional derivative of the node (Jacobian-vector product)
+    /// by forward-mode differentiation.
+    ///
+    /// The tangents are given for variables, and the tangents of other nodes are
+    /// regarded as zero. Values are evaluated by `eval_value` before the sweep.
+    ///
+    /// Returns `NodeTypeError` if a tangent is given for a node other than a variable,
+    /// and `TensorShapeMismatch` if the shape of a tangent differs from its variable.
+    pub fn eval_jvp(
+        &mut self,
+        node: NodeIndex,
+        tangents: &[(NodeIndex, Tensor<A>)],
+    ) -> Result<Tensor<A>> {
+        Ok(self.eval_jvp_many(&[node], tangents)?.remove(0))
+    }
+
+    /// Same as `eval_jvp`, but the directional derivatives of several nodes are
+    /// evaluated in a single forward sweep.
+    pub(crate) fn eval_jvp_many(
+        &mut self,
+        nodes: &[NodeIndex],
+        tangents: &[(NodeIndex, Tensor<A>)],
+    ) -> Result<Vec<Tensor<A>>> {
+        for (var, tangent) in tangents {
+            if !self[*var].is_variable() {
+                return Err(Error::NodeTypeError { index: var.index() });
+            }
+            check_shape(tangent, self.get_value(*var)?.shape())?;
         }
-        let shape = self[node].value.as_ref().unwrap().shape();
-        let one = Tensor::ones(shape);
-        self.deriv_recur(node, one)
+        let values = nodes
+            .iter()
+            .map(|&node| self.eval_value(node))
+            .collect::<Result<Vec<_>>>()?;
+        let mut tan: HashMap<NodeIndex, Tensor<A>> = tangents.iter().cloned().collect();
+        for idx in self.topological_order_many(nodes) {
+            let property = self[idx].property;
+            match property {
+                Property::Variable | Property::Constant => {}
+                Property::Unary(ref op) => {
+                    let arg = self.get_arg1(idx);
+                    if let Some(t) = tan.get(&arg).cloned() {
+                        tan.insert(idx, op.eval_jvp(self.get_value(arg)?, t));
+                    }
+                }
+                Property::Binary(ref op) => {
+                    let (lhs, rhs) = self.get_arg2(idx);
+                    if !tan.contains_key(&lhs) && !tan.contains_key(&rhs) {
+                        continue;
+                    }
+                    let lv = self.get_value(lhs)?;
+                    let rv = self.get_value(rhs)?;
+                    let lt = tan
+                        .get(&lhs)
+                        .cloned()
+                        .unwrap_or_else(|| Tensor::zeros(lv.shape()));
+                    let rt = tan
+                        .get(&rhs)
+                        .cloned()
+                        .unwrap_or_else(|| Tensor::zeros(rv.shape()));
+                    tan.insert(idx, op.eval_jvp(lv, rv, lt, rt));
+                }
+                Property::Custom(id) => {
+                    let args = self.args(idx);
+                    if args.iter().all(|arg| !tan.contains_key(arg)) {
+                        continue;
+                    }
+                    let values = args
+                        .iter()
+                        .map(|&arg| self.get_value(arg))
+                        .collect::<Result<Vec<_>>>()?;
+                    let tangents: Vec<Tensor<A>> = args
+                        .iter()
+                        .zip(values.iter())
+                        .map(|(arg, v)| {
+                            tan.get(arg)
+                                .cloned()
+                                .unwrap_or_else(|| Tensor::zeros(v.shape()))
+                        })
+                        .collect();
+                    tan.insert(idx, self.operators[id].eval_jvp(&values, &tangents));
+                }
+            }
+        }
+        Ok(nodes
+            .iter()
+            .zip(values)
+            .map(|(node, value)| {
+                tan.get(node)
+                    .cloned()
+                    .unwrap_or_else(|| Tensor::zeros(value.shape()))
+            })
+            .collect())
+    }
+
+    /// Evaluate the Jacobian of the node with respect to the variables.
+    ///
+    /// The Jacobian for each variable has the shape `node.shape ++ var.shape`.
+    /// Forward sweeps (one per element of the variables) or reverse sweeps
+    /// (one per element of the node) are used, whichever is fewer.
+    /// The sweeps keep their derivatives apart, and `Node::deriv` of the graph is left untouched
+    /// even in the accumulation mode.
+    /// Returns `NodeTypeError` if one of `vars` is not a variable.
+    pub fn jacobian(&mut self, node: NodeIndex, vars: &[NodeIndex]) -> Result<Vec<Tensor<A>>> {
+        if let Some(&var) = vars.iter().find(|&&var| !self[var].is_variable()) {
+            return Err(Error::NodeTypeError { index: var.index() });
+        }
+        let out_shape = self.eval_value(node)?.shape().to_vec();
+        let var_shapes = vars
+            .iter()
+            .map(|&var| Ok(self.get_value(var)?.shape().to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        let m: usize = out_shape.iter().product();
+        let sizes: Vec<usize> = var_shapes.iter().map(|s| s.iter().product()).collect();
+        let mut jac: Vec<_> = sizes.iter().map(|&n| Array2::zeros((m, n))).collect();
+
+        if sizes.iter().sum::<usize>() <= m {
+            for (j, (&var, shape)) in vars.iter().zip(var_shapes.iter()).enumerate() {
+                for k in 0..sizes[j] {
+                    let column = self.eval_jvp(node, &[(var, unit_tensor(shape, k))])?;
+                    for (a, d) in column.iter().enumerate() {
+                        jac[j][(a, k)] = *d;
+                    }
+                }
+            }
+        } else {
+            for a in 0..m {
+                let mut scratch = Scratch::new(self);
+                scratch.backward(vec![(node, unit_tensor(&out_shape, a))])?;
+                for (j, &var) in vars.iter().enumerate() {
+                    if let Some(row) = scratch.get_deriv(var) {
+                        for (k, d) in row.iter().enumerate() {
+                            jac[j][(a, k)] = *d;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(jac
+            .into_iter()
+            .zip(var_shapes.iter())
+            .map(|(jac, var_shape)| {
+                let shape: Vec<usize> = out_shape.iter().chain(var_shape.iter()).cloned().collect();
+                jac.into_shape(ndarray::IxDyn(&shape))
+                    .unwrap()
+                    .into_shared()
+            })
+            .collect())
     }
 
     pub fn to_dot(&self, sink: &mut impl io::Write) -> io::Result<()>
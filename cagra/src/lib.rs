@@ -16,7 +16,7 @@
 //! Create a graph for `z = (x + y) - 2*x*y`
 //!
 //! ```
-//! # use approx::abs_diff_eq;
+//! # use approx::assert_abs_diff_eq;
 //! use cagra::{graph::*, tensor::*};
 //!
 //! let mut g: Graph<f64> = Graph::new();
@@ -29,13 +29,13 @@
 //! let sum = g.sub(x_y, axy);
 //!
 //! let result = g.eval_value(sum).unwrap().as_scalar().unwrap();
-//! abs_diff_eq!(result, -2.0);
+//! assert_abs_diff_eq!(result, -2.0);
 //!
 //! g.eval_deriv(sum);
 //! let dx = g.get_deriv(x).unwrap().as_scalar().unwrap();
 //! let dy = g.get_deriv(y).unwrap().as_scalar().unwrap();
-//! abs_diff_eq!(dx, -5.0);
-//! abs_diff_eq!(dy, -1.0);
+//! assert_abs_diff_eq!(dx, -5.0);
+//! assert_abs_diff_eq!(dy, -1.0);
 //! ```
 
 #[doc(hidden)]
@@ -43,6 +43,18 @@ pub extern crate cagra_parser as parser;
 
 #[macro_use]
 pub mod graph;
+pub mod batch;
+pub mod check;
+pub mod checkpoint;
+pub mod compose;
 pub mod error;
 pub mod operator;
+pub mod optimize;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod rewrite;
+pub mod serialize;
+pub mod session;
+pub mod symbolic;
+pub mod tape;
 pub mod tensor;
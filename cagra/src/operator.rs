@@ -3,10 +3,40 @@
 use cauchy::Scalar;
 use ndarray::azip;
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
 
 use crate::tensor::*;
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+/// `out[i] = f(arg[i])` for the tensors of the same shape
+fn map_into<A: Scalar, F: Fn(A) -> A>(arg: &Tensor<A>, out: &mut Tensor<A>, f: F) {
+    // fast path for contiguous tensors
+    if let (Some(o), Some(a)) = (out.as_slice_mut(), arg.as_slice()) {
+        for (o, a) in o.iter_mut().zip(a) {
+            *o = f(*a);
+        }
+        return;
+    }
+    azip!(mut out (out.view_mut()), arg (arg.view()) in { *out = f(arg) });
+}
+
+/// `out[i] = f(lhs[i], rhs[i])` for the tensors of the same shape
+fn zip_into<A: Scalar, F: Fn(A, A) -> A>(
+    lhs: &Tensor<A>,
+    rhs: &Tensor<A>,
+    out: &mut Tensor<A>,
+    f: F,
+) {
+    // fast path for contiguous tensors
+    if let (Some(o), Some(l), Some(r)) = (out.as_slice_mut(), lhs.as_slice(), rhs.as_slice()) {
+        for (o, (l, r)) in o.iter_mut().zip(l.iter().zip(r)) {
+            *o = f(*l, *r);
+        }
+        return;
+    }
+    azip!(mut out (out.view_mut()), lhs (lhs.view()), rhs (rhs.view()) in { *out = f(lhs, rhs) });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Unary {
     Neg,
     Square,
@@ -18,6 +48,8 @@ pub enum Unary {
     Sinh,
     Cosh,
     Tanh,
+    /// Identity in value, but the derivative does not flow into the argument
+    StopGradient,
 }
 
 impl Unary {
@@ -34,11 +66,34 @@ impl Unary {
             Unary::Sinh => arg.mapv_into(|a| a.sinh()),
             Unary::Cosh => arg.mapv_into(|a| a.cosh()),
             Unary::Tanh => arg.mapv_into(|a| a.tanh()),
+            Unary::StopGradient => arg,
+        }
+    }
+
+    /// Evaluate the result value of the operator into `out`,
+    /// which must have the same shape as the argument.
+    ///
+    /// The buffer of `out` is reused unless it is shared.
+    pub fn eval_value_into<A: Scalar>(&self, arg: &Tensor<A>, out: &mut Tensor<A>) {
+        match self {
+            Unary::Neg => map_into(arg, out, |a| -a),
+            Unary::Square => map_into(arg, out, |a| a.conj() * a),
+            Unary::Ln => map_into(arg, out, |a| a.ln()),
+            Unary::Exp => map_into(arg, out, |a| a.exp()),
+            Unary::Sin => map_into(arg, out, |a| a.sin()),
+            Unary::Cos => map_into(arg, out, |a| a.cos()),
+            Unary::Tan => map_into(arg, out, |a| a.tan()),
+            Unary::Sinh => map_into(arg, out, |a| a.sinh()),
+            Unary::Cosh => map_into(arg, out, |a| a.cosh()),
+            Unary::Tanh => map_into(arg, out, |a| a.tanh()),
+            Unary::StopGradient => map_into(arg, out, |a| a),
         }
     }
 
     /// Evaluate the derivative of the operator multiplied by the received
     /// derivative from upper of the graph.
+    ///
+    /// This is zero for `StopGradient`, which is not propagated at all in the graph.
     pub fn eval_deriv<A: Scalar>(&self, arg: Tensor<A>, mut deriv: Tensor<A>) -> Tensor<A> {
         match self {
             Unary::Neg => {
@@ -60,7 +115,7 @@ impl Unary {
                 azip!(mut deriv, arg in { *deriv *= -arg.sin() });
             }
             Unary::Tan => {
-                azip!(mut deriv, arg in { *deriv /= -arg.cos() * arg.cos() });
+                azip!(mut deriv, arg in { *deriv /= arg.cos() * arg.cos() });
             }
             Unary::Sinh => {
                 azip!(mut deriv, arg in { *deriv *= arg.cosh() });
@@ -71,12 +126,23 @@ impl Unary {
             Unary::Tanh => {
                 azip!(mut deriv, arg in { *deriv /= arg.cosh() * arg.cosh() });
             }
+            Unary::StopGradient => {
+                deriv.fill(A::zero());
+            }
         }
         deriv
     }
+
+    /// Evaluate the derivative of the operator multiplied by the tangent of the argument.
+    ///
+    /// Since every unary operator acts element-wise, its Jacobian is diagonal,
+    /// and the Jacobian-vector product coincides with `eval_deriv`.
+    pub fn eval_jvp<A: Scalar>(&self, arg: Tensor<A>, tangent: Tensor<A>) -> Tensor<A> {
+        self.eval_deriv(arg, tangent)
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Binary {
     Add,
     Mul,
@@ -94,6 +160,35 @@ impl Binary {
             Binary::Dot => (lhs * rhs).sum().into_tensor(),
         }
     }
+    /// Evaluate the result value of the operator into `out`.
+    ///
+    /// The buffer of `out` is reused unless it is shared, if the arguments have the same
+    /// shape and `out` has the shape of the result.
+    /// Otherwise, e.g. when an argument is broadcast, a new buffer is allocated.
+    pub fn eval_value_into<A: Scalar>(
+        &self,
+        lhs: &Tensor<A>,
+        rhs: &Tensor<A>,
+        out: &mut Tensor<A>,
+    ) {
+        let same_shape = lhs.shape() == rhs.shape();
+        match self {
+            Binary::Dot if same_shape && out.ndim() == 0 => {
+                out.fill(lhs.iter().zip(rhs.iter()).map(|(l, r)| *l * *r).sum());
+            }
+            Binary::Add if same_shape && out.shape() == lhs.shape() => {
+                zip_into(lhs, rhs, out, |l, r| l + r)
+            }
+            Binary::Mul if same_shape && out.shape() == lhs.shape() => {
+                zip_into(lhs, rhs, out, |l, r| l * r)
+            }
+            Binary::Div if same_shape && out.shape() == lhs.shape() => {
+                zip_into(lhs, rhs, out, |l, r| l / r)
+            }
+            _ => *out = self.eval_value(lhs.clone(), rhs.clone()),
+        }
+    }
+
     /// Evaluate the derivative of the operator multiplied by the received
     /// derivative from upper of the graph.
     pub fn eval_deriv<A: Scalar>(
@@ -115,4 +210,46 @@ impl Binary {
             }
         }
     }
+
+    /// Evaluate the derivative of the operator multiplied by the tangents of the arguments.
+    pub fn eval_jvp<A: Scalar>(
+        &self,
+        lhs: Tensor<A>,
+        rhs: Tensor<A>,
+        l_tan: Tensor<A>,
+        r_tan: Tensor<A>,
+    ) -> Tensor<A> {
+        match self {
+            Binary::Add => l_tan + r_tan,
+            Binary::Mul => l_tan * rhs.clone() + lhs * r_tan,
+            Binary::Div => l_tan / rhs.clone() - lhs * r_tan / (rhs.clone() * rhs.clone()),
+            Binary::Dot => ((l_tan * rhs).sum() + (lhs * r_tan).sum()).into_tensor(),
+        }
+    }
+}
+
+/// User-defined operator taking any number of arguments
+///
+/// Custom operators are appended to the graph by `Graph::custom`.
+/// They are identified by `name` when the graph is serialized,
+/// and rebuilt from `params` by `serialize::Registry` when deserialized.
+/// As the built-in operators, they must be pure functions of the arguments.
+pub trait Operator<A: Scalar>: fmt::Debug + Send + Sync {
+    /// Name of the operator in the registry
+    fn name(&self) -> String;
+
+    /// Parameters to rebuild the operator from the registry
+    fn params(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    /// Evaluate the result value of the operator
+    fn eval_value(&self, args: &[Tensor<A>]) -> Tensor<A>;
+
+    /// Evaluate the derivatives of the operator with respect to each argument
+    /// multiplied by the received derivative from upper of the graph.
+    fn eval_deriv(&self, args: &[Tensor<A>], deriv: Tensor<A>) -> Vec<Tensor<A>>;
+
+    /// Evaluate the derivative of the operator multiplied by the tangents of the arguments.
+    fn eval_jvp(&self, args: &[Tensor<A>], tangents: &[Tensor<A>]) -> Tensor<A>;
 }
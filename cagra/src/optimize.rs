@@ -0,0 +1,163 @@
+//! Optimization passes rewriting the graph
+//!
+//! The passes keep the values of every node reachable from the named nodes,
+//! and the names registered in the namespace keep pointing to the nodes of the same meaning.
+
+use cauchy::Scalar;
+use num_traits::ToPrimitive;
+use petgraph::prelude::*;
+use petgraph::stable_graph::StableGraph;
+use std::collections::{HashMap, HashSet};
+
+use crate::error::Result;
+use crate::graph::{Graph, Property, Tensor};
+use crate::operator::Binary;
+
+impl<A: Scalar> Graph<A> {
+    /// Nodes of the whole graph in topological order
+    pub(crate) fn toposort(&self) -> Vec<NodeIndex> {
+        petgraph::algo::toposort(&self.graph, None).expect("Calculation graph must be acyclic")
+    }
+
+    /// Evaluate the operators whose arguments are all constants,
+    /// and replace them by constant nodes holding the results.
+    ///
+    /// Subtrees of constants are folded into a single constant from the leaves.
+    /// The nodes are replaced in place, and thus the indices and names are kept valid.
+    /// The leaf constants are left in the graph even if no longer used,
+    /// and are removed by `prune`.
+    /// Returns the number of folded nodes.
+    pub fn fold_constants(&mut self) -> Result<usize> {
+        let mut folded = 0;
+        for node in self.toposort() {
+            match self[node].property {
+                Property::Unary(_) | Property::Binary(_) | Property::Custom(_) => {}
+                Property::Variable | Property::Constant => continue,
+            }
+            let foldable = self
+                .graph
+                .neighbors_directed(node, Direction::Incoming)
+                .all(|arg| self[arg].is_constant());
+            if !foldable {
+                continue;
+            }
+            let value = self.eval_node(node, |n| self.get_value(n))?;
+            self.set_args(node, &[]);
+            self[node].value = Some(value);
+            self[node].property = Property::Constant;
+            folded += 1;
+        }
+        Ok(folded)
+    }
+
+    /// Merge structurally identical nodes, i.e. the operators of the same kind applied
+    /// to the same arguments, and the constants of the same value.
+    ///
+    /// The arguments of commutative operators (`Add` and `Mul`) are compared as unordered.
+    /// Variables are never merged.
+    /// The graph is rebuilt, and the names are redirected to the new nodes.
+    /// Returns the map from the old indices to the new indices.
+    pub fn eliminate_common_subexpressions(&mut self) -> HashMap<NodeIndex, NodeIndex> {
+        let mut graph = StableGraph::new();
+        let mut remap: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut operators: HashMap<(Property, Vec<NodeIndex>), NodeIndex> = HashMap::new();
+        let mut constants: HashMap<ConstantKey, NodeIndex> = HashMap::new();
+        for node in self.toposort() {
+            // arguments in the order of lhs, rhs
+            let args: Vec<NodeIndex> = self.args(node).iter().map(|arg| remap[arg]).collect();
+            let property = self[node].property;
+            let mut operands = args.clone();
+            if let Property::Binary(Binary::Add) | Property::Binary(Binary::Mul) = property {
+                operands.sort();
+            }
+            let key = (property, operands);
+            let existing = match property {
+                Property::Variable => None,
+                Property::Constant => self[node]
+                    .value
+                    .as_ref()
+                    .and_then(|value| constants.get(&constant_key(value)))
+                    .cloned(),
+                Property::Unary(_) | Property::Binary(_) | Property::Custom(_) => {
+                    operators.get(&key).cloned()
+                }
+            };
+            let new = match existing {
+                Some(new) => new,
+                None => {
+                    let new = graph.add_node(self[node].clone());
+                    for arg in args {
+                        graph.add_edge(arg, new, ());
+                    }
+                    match property {
+                        Property::Variable => {}
+                        Property::Constant => {
+                            let value = self.get_value(node).expect("Constant must have value");
+                            constants.insert(constant_key(&value), new);
+                        }
+                        Property::Unary(_) | Property::Binary(_) | Property::Custom(_) => {
+                            operators.insert(key, new);
+                        }
+                    }
+                    new
+                }
+            };
+            remap.insert(node, new);
+        }
+        self.graph = graph;
+        for index in self.namespace.values_mut() {
+            *index = remap[index];
+        }
+        remap
+    }
+
+    /// Remove the nodes except the ancestors of the outputs and the named variables.
+    ///
+    /// Names of the removed nodes are removed from the namespace.
+    /// Returns the map from the old indices of the kept nodes to the new indices.
+    pub fn prune(&mut self, outputs: &[NodeIndex]) -> HashMap<NodeIndex, NodeIndex> {
+        let mut keep: HashSet<NodeIndex> = HashSet::new();
+        for &output in outputs {
+            keep.extend(self.topological_order(output));
+        }
+        keep.extend(
+            self.namespace
+                .values()
+                .filter(|&&n| self[n].is_variable())
+                .cloned(),
+        );
+
+        let mut graph = StableGraph::new();
+        let mut remap = HashMap::new();
+        for node in self.toposort() {
+            if !keep.contains(&node) {
+                continue;
+            }
+            let new = graph.add_node(self[node].clone());
+            for arg in self.args(node) {
+                graph.add_edge(remap[&arg], new, ());
+            }
+            remap.insert(node, new);
+        }
+        self.graph = graph;
+        self.namespace = self
+            .namespace
+            .drain()
+            .filter_map(|(name, n)| remap.get(&n).map(|&new| (name, new)))
+            .collect();
+        remap
+    }
+}
+
+/// Shape and the bit patterns of the real and imaginary parts of the elements
+type ConstantKey = (Vec<usize>, Vec<u64>);
+
+/// Constants are compared bitwise, which distinguishes `0.0` from `-0.0`
+fn constant_key<A: Scalar>(value: &Tensor<A>) -> ConstantKey {
+    let bits = |r: A::Real| r.to_f64().expect("Real must be a float").to_bits();
+    let elements = value
+        .iter()
+        .flat_map(|a| [bits(a.re()), bits(a.im())])
+        .collect();
+    (value.shape().to_vec(), elements)
+}
@@ -0,0 +1,117 @@
+//! Parallel evaluation of independent subgraphs
+//!
+//! Enabled by the `parallel` feature. The nodes are grouped into levels of mutually
+//! independent nodes, and each level is evaluated on the rayon thread pool
+//! (the global pool, or the one installed by `ThreadPool::install`).
+//! The derivatives from the consumers of a node are summed up in the same order as
+//! `Graph::eval_deriv`, and thus the results match the serial ones bit for bit.
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::graph::{Graph, Storage, Tensor};
+use crate::tensor::check_shape;
+
+/// Group the nodes into levels such that every node comes after its dependencies.
+/// Dependencies out of `order` are regarded as already resolved.
+/// Nodes in each level keep the relative order in `order`.
+fn levels<F>(order: &[NodeIndex], deps: F) -> Vec<Vec<NodeIndex>>
+where
+    F: Fn(NodeIndex) -> Vec<NodeIndex>,
+{
+    let mut level_of: HashMap<NodeIndex, usize> = HashMap::new();
+    let mut levels: Vec<Vec<NodeIndex>> = Vec::new();
+    for &n in order {
+        let level = deps(n)
+            .iter()
+            .filter_map(|d| level_of.get(d))
+            .map(|l| l + 1)
+            .max()
+            .unwrap_or(0);
+        if levels.len() <= level {
+            levels.push(Vec::new());
+        }
+        levels[level].push(n);
+        level_of.insert(n, level);
+    }
+    levels
+}
+
+impl<A: Scalar + Send + Sync> Graph<A> {
+    /// Parallel version of `Graph::eval_value`
+    pub fn eval_value_par(&mut self, node: NodeIndex) -> Result<Tensor<A>> {
+        let dirty = self.topological_order_filtered(node, |n| self[n].value.is_none());
+        let levels = levels(&dirty, |n| {
+            self.graph
+                .neighbors_directed(n, Direction::Incoming)
+                .collect()
+        });
+        for level in levels {
+            let values = level
+                .par_iter()
+                .map(|&n| self.eval_node(n, |m| self.get_value(m)))
+                .collect::<Result<Vec<_>>>()?;
+            for (n, value) in level.into_iter().zip(values) {
+                self[n].value = Some(value);
+            }
+        }
+        self.get_value(node)
+    }
+
+    /// Parallel version of `Graph::eval_deriv`
+    pub fn eval_deriv_par(&mut self, node: NodeIndex) -> Result<()> {
+        let one = Tensor::ones(self.get_value(node)?.shape());
+        self.eval_deriv_with_seed_par(node, one)
+    }
+
+    /// Parallel version of `Graph::eval_deriv_with_seed`
+    pub fn eval_deriv_with_seed_par(&mut self, node: NodeIndex, seed: Tensor<A>) -> Result<()> {
+        check_shape(&seed, self.get_value(node)?.shape())?;
+        self.with_cleared_derivs(|g| g.backward_par(node, seed))
+    }
+
+    fn backward_par(&mut self, node: NodeIndex, seed: Tensor<A>) -> Result<()> {
+        // reverse topological order, which is the order of the serial sweep
+        let order: Vec<NodeIndex> = self.topological_order(node).into_iter().rev().collect();
+        let position: HashMap<NodeIndex, usize> =
+            order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let levels = levels(&order, |n| {
+            self.graph
+                .neighbors_directed(n, Direction::Outgoing)
+                .collect()
+        });
+
+        // derivatives received by each node, tagged by the position of the sender
+        let mut received: HashMap<NodeIndex, Vec<(usize, Tensor<A>)>> = HashMap::new();
+        received.insert(node, vec![(0, seed)]);
+        for level in levels {
+            for &n in &level {
+                if let Some(mut ders) = received.remove(&n) {
+                    ders.sort_by_key(|(pos, _)| *pos);
+                    let mut ders = ders.into_iter().map(|(_, der)| der);
+                    let first = ders.next().unwrap();
+                    self[n].deriv = Some(ders.fold(first, |sum, der| sum + der));
+                }
+            }
+            let propagated = level
+                .par_iter()
+                .map(|&n| match &self[n].deriv {
+                    Some(der) => self.deriv_node(n, der.clone(), |m| self.get_value(m)),
+                    None => Ok(Vec::new()),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for (n, propagated) in level.into_iter().zip(propagated) {
+                for (arg, der) in propagated {
+                    received
+                        .entry(arg)
+                        .or_insert_with(Vec::new)
+                        .push((position[&n], der));
+                }
+            }
+        }
+        Ok(())
+    }
+}
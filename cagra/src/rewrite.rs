@@ -0,0 +1,289 @@
+//! Algebraic simplification by rewrite rules
+//!
+//! `Rewriter` applies rules to the operator nodes from the leaves to the outputs
+//! until no rule applies. A rewritten node is not removed from the graph and is still
+//! evaluated correctly, but its consumers and names are redirected to the replacement.
+//! Use `Graph::prune` to remove them.
+//! Since `Graph::sub` is lowered as `add(lhs, neg(rhs))`, the rules see subtractions
+//! in this form.
+//!
+//! Wildcards match nodes by index, e.g. `x/x` matches only if both arguments are the
+//! same node. Run `Graph::eliminate_common_subexpressions` beforehand to merge
+//! structurally identical arguments.
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Node, Property, Tensor};
+use crate::operator::{Binary, Unary};
+
+/// Pattern of a subgraph, used both for matching and for building the replacement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern<A> {
+    /// Any node. Wildcards of the same id must match the same node.
+    Any(usize),
+    /// Scalar (0-dimensional) constant of the value
+    Scalar(A),
+    /// Unary operator applied to the argument
+    Unary(Unary, Box<Pattern<A>>),
+    /// Binary operator applied to the arguments. The order of the arguments matters.
+    Binary(Binary, Box<Pattern<A>>, Box<Pattern<A>>),
+}
+
+impl<A> Pattern<A> {
+    pub fn unary(op: Unary, arg: Pattern<A>) -> Self {
+        Pattern::Unary(op, Box::new(arg))
+    }
+
+    pub fn binary(op: Binary, lhs: Pattern<A>, rhs: Pattern<A>) -> Self {
+        Pattern::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    /// Ids of wildcards in the pattern
+    fn wildcards(&self, ids: &mut Vec<usize>) {
+        match self {
+            Pattern::Any(id) => ids.push(*id),
+            Pattern::Scalar(_) => {}
+            Pattern::Unary(_, arg) => arg.wildcards(ids),
+            Pattern::Binary(_, lhs, rhs) => {
+                lhs.wildcards(ids);
+                rhs.wildcards(ids);
+            }
+        }
+    }
+}
+
+/// Rewrite rule of a node
+pub trait Rule<A: Scalar> {
+    /// Returns the node replacing `node` if the rule applies.
+    /// The replacement may be appended to the graph, but existing nodes must not be changed.
+    fn apply(&self, graph: &mut Graph<A>, node: NodeIndex) -> Option<NodeIndex>;
+}
+
+/// Rule replacing the subgraph matching `pattern` by `replacement`
+#[derive(Debug, Clone)]
+pub struct PatternRule<A> {
+    pattern: Pattern<A>,
+    replacement: Pattern<A>,
+}
+
+impl<A> PatternRule<A> {
+    /// Every wildcard in the replacement has to appear in the pattern.
+    pub fn new(pattern: Pattern<A>, replacement: Pattern<A>) -> Result<Self> {
+        let mut bound = Vec::new();
+        pattern.wildcards(&mut bound);
+        let mut used = Vec::new();
+        replacement.wildcards(&mut used);
+        if let Some(&id) = used.iter().find(|id| !bound.contains(id)) {
+            return Err(Error::UnboundWildcard { id });
+        }
+        Ok(Self {
+            pattern,
+            replacement,
+        })
+    }
+}
+
+impl<A: Scalar> Rule<A> for PatternRule<A> {
+    fn apply(&self, graph: &mut Graph<A>, node: NodeIndex) -> Option<NodeIndex> {
+        let mut bindings = HashMap::new();
+        if !graph.match_pattern(&self.pattern, node, &mut bindings) {
+            return None;
+        }
+        Some(graph.build_pattern(&self.replacement, &bindings))
+    }
+}
+
+/// `x/x` to ones and `x + (-x)` to zeros, assuming `x` is nonzero for the former.
+///
+/// The shape of the result is taken from the cached value of `x`,
+/// and thus the rule applies only if `x` has been evaluated.
+struct Cancellation;
+
+impl<A: Scalar> Rule<A> for Cancellation {
+    fn apply(&self, graph: &mut Graph<A>, node: NodeIndex) -> Option<NodeIndex> {
+        let (lhs, rhs) = match graph[node].property {
+            Property::Binary(Binary::Div) | Property::Binary(Binary::Add) => graph.get_arg2(node),
+            _ => return None,
+        };
+        let (x, fill) = match graph[node].property {
+            Property::Binary(Binary::Div) if lhs == rhs => (lhs, A::one()),
+            Property::Binary(Binary::Add) if graph.is_negation(rhs, lhs) => (lhs, A::zero()),
+            Property::Binary(Binary::Add) if graph.is_negation(lhs, rhs) => (rhs, A::zero()),
+            _ => return None,
+        };
+        let shape = graph[x].value.as_ref()?.shape().to_vec();
+        Some(graph.constant(Tensor::from_elem(shape, fill)))
+    }
+}
+
+/// Set of rewrite rules
+pub struct Rewriter<A: Scalar> {
+    rules: Vec<Box<dyn Rule<A>>>,
+}
+
+impl<A: Scalar + 'static> Default for Rewriter<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Scalar + 'static> Rewriter<A> {
+    /// Rewriter with the built-in identities:
+    /// `x*1`, `1*x`, `x+0`, `0+x`, `-(-x)`, `ln(exp(x))` to `x`,
+    /// `x/x` to ones, and `x + (-x)` to zeros.
+    ///
+    /// `ln(exp(x)) = x` holds only for real scalars, and `x/x = 1` only for nonzero `x`.
+    pub fn new() -> Self {
+        let x = || Pattern::Any(0);
+        let one = || Pattern::Scalar(A::one());
+        let zero = || Pattern::Scalar(A::zero());
+        let identities = vec![
+            (Pattern::binary(Binary::Mul, x(), one()), x()),
+            (Pattern::binary(Binary::Mul, one(), x()), x()),
+            (Pattern::binary(Binary::Add, x(), zero()), x()),
+            (Pattern::binary(Binary::Add, zero(), x()), x()),
+            (
+                Pattern::unary(Unary::Neg, Pattern::unary(Unary::Neg, x())),
+                x(),
+            ),
+            (
+                Pattern::unary(Unary::Ln, Pattern::unary(Unary::Exp, x())),
+                x(),
+            ),
+        ];
+        let mut rewriter = Self::empty();
+        for (pattern, replacement) in identities {
+            rewriter.add_pattern(pattern, replacement).unwrap();
+        }
+        rewriter.add_rule(Box::new(Cancellation));
+        rewriter
+    }
+
+    /// Rewriter without rules
+    pub fn empty() -> Self {
+        Rewriter { rules: Vec::new() }
+    }
+
+    /// Register a rule. Rules are tried in the order of registration.
+    pub fn add_rule(&mut self, rule: Box<dyn Rule<A>>) {
+        self.rules.push(rule);
+    }
+
+    /// Register a rule replacing `pattern` by `replacement`
+    pub fn add_pattern(&mut self, pattern: Pattern<A>, replacement: Pattern<A>) -> Result<()> {
+        self.add_rule(Box::new(PatternRule::new(pattern, replacement)?));
+        Ok(())
+    }
+
+    /// Rewrite the graph until no rule applies,
+    /// and returns the map from the rewritten nodes to their replacements.
+    ///
+    /// Rules must not produce a node which the rules rewrite again into the same form,
+    /// e.g. swapping the arguments, or this does not terminate.
+    pub fn rewrite(&self, graph: &mut Graph<A>) -> HashMap<NodeIndex, NodeIndex> {
+        let mut replaced = HashMap::new();
+        loop {
+            let mut changed = false;
+            for node in graph.toposort() {
+                let leaf = graph[node].is_variable() || graph[node].is_constant();
+                if leaf || replaced.contains_key(&node) {
+                    continue;
+                }
+                for rule in &self.rules {
+                    match rule.apply(graph, node) {
+                        Some(new) if new != node => {
+                            graph.redirect(node, new);
+                            replaced.insert(node, new);
+                            changed = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        // resolve chains of replacements
+        let mut remap = HashMap::new();
+        for &node in replaced.keys() {
+            let mut new = node;
+            while let Some(&next) = replaced.get(&new) {
+                new = next;
+            }
+            remap.insert(node, new);
+        }
+        remap
+    }
+}
+
+impl<A: Scalar + 'static> Graph<A> {
+    /// Simplify the graph by the built-in identities of `Rewriter::new`
+    pub fn simplify(&mut self) -> HashMap<NodeIndex, NodeIndex> {
+        Rewriter::new().rewrite(self)
+    }
+}
+
+impl<A: Scalar> Graph<A> {
+    /// Check `neg` is `-x`
+    fn is_negation(&self, neg: NodeIndex, x: NodeIndex) -> bool {
+        match self[neg].property {
+            Property::Unary(Unary::Neg) => self.get_arg1(neg) == x,
+            _ => false,
+        }
+    }
+
+    fn match_pattern(
+        &self,
+        pattern: &Pattern<A>,
+        node: NodeIndex,
+        bindings: &mut HashMap<usize, NodeIndex>,
+    ) -> bool {
+        match (pattern, self[node].property) {
+            (Pattern::Any(id), _) => *bindings.entry(*id).or_insert(node) == node,
+            (Pattern::Scalar(a), Property::Constant) => match &self[node].value {
+                Some(value) => value.ndim() == 0 && value.iter().all(|v| v == a),
+                None => false,
+            },
+            (Pattern::Unary(op, arg), Property::Unary(unary)) => {
+                *op == unary && self.match_pattern(arg, self.get_arg1(node), bindings)
+            }
+            (Pattern::Binary(op, lhs, rhs), Property::Binary(binary)) => {
+                let (l, r) = self.get_arg2(node);
+                *op == binary
+                    && self.match_pattern(lhs, l, bindings)
+                    && self.match_pattern(rhs, r, bindings)
+            }
+            _ => false,
+        }
+    }
+
+    fn build_pattern(
+        &mut self,
+        pattern: &Pattern<A>,
+        bindings: &HashMap<usize, NodeIndex>,
+    ) -> NodeIndex {
+        match pattern {
+            Pattern::Any(id) => bindings[id],
+            Pattern::Scalar(a) => self.constant_scalar(*a),
+            Pattern::Unary(op, arg) => {
+                let arg = self.build_pattern(arg, bindings);
+                let n = self.graph.add_node(Node::from(*op));
+                self.graph.add_edge(arg, n, ());
+                n
+            }
+            Pattern::Binary(op, lhs, rhs) => {
+                let lhs = self.build_pattern(lhs, bindings);
+                let rhs = self.build_pattern(rhs, bindings);
+                let n = self.graph.add_node(Node::from(*op));
+                self.graph.add_edge(lhs, n, ());
+                self.graph.add_edge(rhs, n, ());
+                n
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! JSON serialization of graphs
+//!
+//! The values of variables and constants are stored, and the cached values of operators
+//! are not. Custom operators are stored by their names and parameters,
+//! and rebuilt by the constructors registered in `Registry`.
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Node, Property, Tensor};
+use crate::operator::{Binary, Operator, Unary};
+
+/// Constructor of a custom operator from its parameters
+pub type Constructor<A> = Box<dyn Fn(&serde_json::Value) -> Result<Box<dyn Operator<A>>>>;
+
+/// Named constructors of custom operators
+pub struct Registry<A: Scalar> {
+    constructors: HashMap<String, Constructor<A>>,
+}
+
+impl<A: Scalar> Default for Registry<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Scalar> Registry<A> {
+    pub fn new() -> Self {
+        Registry {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Register the constructor of the operator named `name`, which receives `Operator::params`.
+    /// Returns `DuplicatedName` if the name is already registered.
+    pub fn register<F>(&mut self, name: &str, constructor: F) -> Result<()>
+    where
+        F: Fn(&serde_json::Value) -> Result<Box<dyn Operator<A>>> + 'static,
+    {
+        if self.constructors.contains_key(name) {
+            return Err(Error::DuplicatedName { name: name.into() });
+        }
+        self.constructors.insert(name.into(), Box::new(constructor));
+        Ok(())
+    }
+
+    fn build(&self, name: &str, params: &serde_json::Value) -> Result<Box<dyn Operator<A>>> {
+        match self.constructors.get(name) {
+            Some(constructor) => constructor(params),
+            None => Err(Error::UnregisteredOperator { name: name.into() }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Kind {
+    Variable,
+    Constant,
+    Unary(Unary),
+    Binary(Binary),
+    Custom {
+        name: String,
+        params: serde_json::Value,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeData<A> {
+    kind: Kind,
+    /// Positions of the arguments in `GraphData::nodes`
+    args: Vec<usize>,
+    value: Option<Tensor<A>>,
+    #[serde(default)]
+    checkpoint: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GraphData<A> {
+    /// Nodes in topological order
+    nodes: Vec<NodeData<A>>,
+    names: BTreeMap<String, usize>,
+}
+
+impl<A: Scalar + Serialize> Graph<A> {
+    /// Serialize the graph into JSON
+    pub fn to_json(&self) -> Result<String> {
+        let order = self.toposort();
+        let position: HashMap<NodeIndex, usize> =
+            order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let nodes = order
+            .iter()
+            .map(|&n| {
+                let (kind, value) = match self[n].property {
+                    Property::Variable => (Kind::Variable, self[n].value.clone()),
+                    Property::Constant => (Kind::Constant, self[n].value.clone()),
+                    Property::Unary(op) => (Kind::Unary(op), None),
+                    Property::Binary(op) => (Kind::Binary(op), None),
+                    Property::Custom(id) => {
+                        let op = &self.operators[id];
+                        let kind = Kind::Custom {
+                            name: op.name(),
+                            params: op.params(),
+                        };
+                        (kind, None)
+                    }
+                };
+                let args = self.args(n).iter().map(|arg| position[arg]).collect();
+                NodeData {
+                    kind,
+                    args,
+                    value,
+                    checkpoint: self[n].checkpoint,
+                }
+            })
+            .collect();
+        let names = self
+            .namespace
+            .iter()
+            .map(|(name, n)| (name.clone(), position[n]))
+            .collect();
+        serde_json::to_string(&GraphData { nodes, names })
+            .map_err(|error| Error::JSONSerializeFailed { error })
+    }
+}
+
+impl<A: Scalar + DeserializeOwned> Graph<A> {
+    /// Deserialize the graph from JSON created by `to_json`.
+    ///
+    /// Custom operators are rebuilt by the registry.
+    /// The indices of nodes are renumbered, and should be looked up by names.
+    ///
+    /// Returns `MalformedNode` if a node refers to itself or a later node, or has a wrong number
+    /// of arguments for its kind, and `MalformedName` if a name refers to a missing node.
+    pub fn from_json(json: &str, registry: &Registry<A>) -> Result<Self> {
+        let data: GraphData<A> =
+            serde_json::from_str(json).map_err(|error| Error::JSONDeserializeFailed { error })?;
+        let mut g = Graph::new();
+        let mut indices: Vec<NodeIndex> = Vec::with_capacity(data.nodes.len());
+        for (position, node) in data.nodes.into_iter().enumerate() {
+            let malformed = Error::MalformedNode { position };
+            let arity_matched = match node.kind {
+                Kind::Variable | Kind::Constant => node.args.is_empty(),
+                Kind::Unary(_) => node.args.len() == 1,
+                Kind::Binary(_) => node.args.len() == 2,
+                Kind::Custom { .. } => true,
+            };
+            if !arity_matched {
+                return Err(malformed);
+            }
+            // arguments precede the node in topological order
+            let args: Vec<NodeIndex> = node
+                .args
+                .iter()
+                .map(|&i| indices.get(i).cloned())
+                .collect::<Option<_>>()
+                .ok_or(malformed)?;
+            let n = match node.kind {
+                Kind::Variable => g.graph.add_node(Node {
+                    value: node.value,
+                    deriv: None,
+                    checkpoint: false,
+                    property: Property::Variable,
+                }),
+                Kind::Constant => g.graph.add_node(Node {
+                    value: node.value,
+                    deriv: None,
+                    checkpoint: false,
+                    property: Property::Constant,
+                }),
+                Kind::Unary(op) => g.graph.add_node(op.into()),
+                Kind::Binary(op) => g.graph.add_node(op.into()),
+                Kind::Custom { name, params } => g.custom(registry.build(&name, &params)?, &[]),
+            };
+            g.set_args(n, &args);
+            g[n].checkpoint = node.checkpoint;
+            indices.push(n);
+        }
+        for (name, i) in data.names {
+            match indices.get(i) {
+                Some(&n) => g.namespace.insert(name, n),
+                None => return Err(Error::MalformedName { name }),
+            };
+        }
+        Ok(g)
+    }
+}
@@ -0,0 +1,154 @@
+//! Evaluation state separated from the graph structure
+//!
+//! A `Graph` shared through `Arc` is used as an immutable definition,
+//! and each `Session` keeps its own values and derivatives of the nodes.
+//! Several sessions can evaluate the same graph at once, e.g. in different threads
+//! or for different parameter sets, without cloning the graph.
+//!
+//! ```
+//! use cagra::{graph::*, session::*, tensor::*};
+//! use std::sync::Arc;
+//!
+//! let mut g: Graph<f64> = Graph::new();
+//! let x = g.scalar("x", 1.0).unwrap();
+//! let y = g.square(x);
+//! let g = Arc::new(g);
+//!
+//! let mut s1 = Session::new(g.clone());
+//! let mut s2 = Session::new(g.clone());
+//! s2.set_value(x, 3.0.into_tensor()).unwrap();
+//! assert_eq!(s1.eval_value(y).unwrap().as_scalar().unwrap(), 1.0);
+//! assert_eq!(s2.eval_value(y).unwrap().as_scalar().unwrap(), 9.0);
+//! ```
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use petgraph::visit::NodeIndexable;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Storage, Tensor};
+use crate::tensor::check_shape;
+
+/// Values and derivatives of the nodes of a shared `Graph`
+#[derive(Debug, Clone)]
+pub struct Session<A: Scalar> {
+    graph: Arc<Graph<A>>,
+    values: Vec<Option<Tensor<A>>>,
+    derivs: Vec<Option<Tensor<A>>>,
+    accumulate: bool,
+}
+
+impl<A: Scalar> Session<A> {
+    /// New session starting from the values and the accumulation mode of the graph
+    pub fn new(graph: Arc<Graph<A>>) -> Self {
+        // indexed by `NodeIndex::index`, which may skip removed nodes
+        let bound = graph.graph.node_bound();
+        let values = (0..bound)
+            .map(|i| graph.graph.node_weight(NodeIndex::new(i)))
+            .map(|node| node.and_then(|node| node.value.clone()))
+            .collect();
+        let derivs = vec![None; bound];
+        let accumulate = graph.is_accumulating();
+        Session {
+            graph,
+            values,
+            derivs,
+            accumulate,
+        }
+    }
+
+    pub fn graph(&self) -> &Arc<Graph<A>> {
+        &self.graph
+    }
+
+    /// Set a value to a variable node, and returns `NodeTypeError` if the node is an operator.
+    ///
+    /// The values of all nodes depending on the variable are discarded in this session.
+    pub fn set_value(&mut self, node: NodeIndex, value: Tensor<A>) -> Result<()> {
+        if !self.graph[node].is_variable() {
+            return Err(Error::NodeTypeError {
+                index: node.index(),
+            });
+        }
+        self.values[node.index()] = Some(value);
+        for n in self.graph.descendants(node) {
+            self.values[n.index()] = None;
+        }
+        Ok(())
+    }
+
+    pub fn get_value(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        self.values[node.index()]
+            .clone()
+            .ok_or(Error::ValueUninitialized {
+                index: node.index(),
+            })
+    }
+
+    pub fn get_deriv(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        self.derivs[node.index()]
+            .clone()
+            .ok_or(Error::DerivUninitialized {
+                index: node.index(),
+            })
+    }
+
+    /// Evaluate the value of the node, see `Graph::eval_value`
+    pub fn eval_value(&mut self, node: NodeIndex) -> Result<Tensor<A>> {
+        let dirty = self
+            .graph
+            .topological_order_filtered(node, |n| self.values[n.index()].is_none());
+        for idx in dirty {
+            let value = self.graph.eval_node(idx, |n| self.get_value(n))?;
+            self.values[idx.index()] = Some(value);
+        }
+        self.get_value(node)
+    }
+
+    /// Evaluate derivative of the node, see `Graph::eval_deriv`
+    pub fn eval_deriv(&mut self, node: NodeIndex) -> Result<()> {
+        let one = Tensor::ones(self.get_value(node)?.shape());
+        self.eval_deriv_with_seed(node, one)
+    }
+
+    /// Evaluate derivative of the node with the given seed, see `Graph::eval_deriv_with_seed`
+    pub fn eval_deriv_with_seed(&mut self, node: NodeIndex, seed: Tensor<A>) -> Result<()> {
+        check_shape(&seed, self.get_value(node)?.shape())?;
+        self.with_cleared_derivs(|s| s.backward(vec![(node, seed)]))
+    }
+
+    /// Discard the derivatives of all nodes in this session
+    pub fn zero_grad(&mut self) {
+        for der in self.derivs.iter_mut() {
+            *der = None;
+        }
+    }
+
+    /// Switch the accumulation mode of this session, see `Graph::set_accumulate`
+    pub fn set_accumulate(&mut self, accumulate: bool) {
+        self.accumulate = accumulate;
+    }
+
+    pub fn is_accumulating(&self) -> bool {
+        self.accumulate
+    }
+}
+
+impl<A: Scalar> Storage<A> for Session<A> {
+    fn graph(&self) -> &Graph<A> {
+        &self.graph
+    }
+
+    fn value(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        self.get_value(node)
+    }
+
+    fn deriv_mut(&mut self, node: NodeIndex) -> &mut Option<Tensor<A>> {
+        &mut self.derivs[node.index()]
+    }
+
+    fn is_accumulating(&self) -> bool {
+        self.accumulate
+    }
+}
@@ -0,0 +1,251 @@
+//! Symbolic differentiation
+//!
+//! The derivatives are appended to the graph as ordinary `Unary` and `Binary` nodes,
+//! and thus the higher derivatives are obtained by differentiating them again.
+//! Second derivatives of a scalar output are also provided as Hessian-vector products
+//! and dense Hessian blocks, by forward-mode differentiation of the gradient nodes.
+
+use cauchy::Scalar;
+use ndarray::{Array2, IxDyn};
+use petgraph::prelude::*;
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Property, Tensor};
+use crate::operator::{Binary, Unary};
+use crate::tensor::unit_tensor;
+
+impl<A: Scalar> Graph<A> {
+    /// Append nodes computing the derivative of the output with respect to variables,
+    /// and returns the map from the variables to the nodes of derivatives.
+    ///
+    /// As in `eval_deriv`, the output is seeded with ones of its shape,
+    /// which is determined by evaluating the output.
+    /// Variables on which the output does not depend are not contained in the result.
+    ///
+    /// The derivative of `square` assumes real scalars.
+    /// Returns `UnsupportedOperator` without changing the graph
+    /// if the output depends on custom operators.
+    pub fn grad(&mut self, output: NodeIndex) -> Result<HashMap<NodeIndex, NodeIndex>> {
+        let one = Tensor::ones(self.eval_value(output)?.shape());
+        let order = self.topological_order(output);
+        // custom operators have no symbolic derivative
+        if let Some(node) = order
+            .iter()
+            .find(|&&n| matches!(self[n].property, Property::Custom(_)))
+        {
+            return Err(Error::UnsupportedOperator {
+                index: node.index(),
+            });
+        }
+        let mut adjoint = HashMap::new();
+        adjoint.insert(output, self.constant(one));
+        for node in order.into_iter().rev() {
+            let g = match adjoint.get(&node) {
+                Some(g) => *g,
+                None => continue,
+            };
+            match self[node].property {
+                Property::Variable | Property::Constant => {}
+                Property::Unary(Unary::StopGradient) => {}
+                Property::Custom(_) => unreachable!("Custom operators are rejected above"),
+                Property::Unary(op) => {
+                    let x = self.get_arg1(node);
+                    let d = self.unary_adjoint(op, x, node, g);
+                    self.accumulate_adjoint(&mut adjoint, x, d);
+                }
+                Property::Binary(op) => {
+                    let (lhs, rhs) = self.get_arg2(node);
+                    let (l, r) = self.binary_adjoint(op, lhs, rhs, g);
+                    self.accumulate_adjoint(&mut adjoint, lhs, l);
+                    self.accumulate_adjoint(&mut adjoint, rhs, r);
+                }
+            }
+        }
+        Ok(adjoint
+            .into_iter()
+            .filter(|(node, _)| self[*node].is_variable())
+            .collect())
+    }
+
+    /// Hessian-vector product of the scalar output.
+    ///
+    /// Returns the map from every variable `w` to `d/d(var) (d(output)/dw) . v`,
+    /// i.e. the blocks of the Hessian in the column of `var` multiplied by `v`.
+    /// The graph itself is not modified.
+    pub fn hvp(
+        &self,
+        output: NodeIndex,
+        var: NodeIndex,
+        v: Tensor<A>,
+    ) -> Result<HashMap<NodeIndex, Tensor<A>>> {
+        let (mut g, grad) = self.gradient_graph(output)?;
+        let vars = self.variables();
+        let columns = hvp_columns(&mut g, &grad, &vars, var, v)?;
+        Ok(vars.into_iter().zip(columns).collect())
+    }
+
+    /// Dense Hessian of the scalar output with respect to the variables.
+    ///
+    /// The block `[i][j]` has the shape `vars[i].shape ++ vars[j].shape`.
+    /// The graph itself is not modified.
+    pub fn hessian(&self, output: NodeIndex, vars: &[NodeIndex]) -> Result<Vec<Vec<Tensor<A>>>> {
+        let (mut g, grad) = self.gradient_graph(output)?;
+        let shapes = vars
+            .iter()
+            .map(|&var| Ok(g.get_value(var)?.shape().to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut blocks: Vec<Vec<Array2<A>>> = shapes
+            .iter()
+            .map(|shape_i| {
+                let n_i: usize = shape_i.iter().product();
+                shapes
+                    .iter()
+                    .map(|shape_j| Array2::zeros((n_i, shape_j.iter().product())))
+                    .collect()
+            })
+            .collect();
+        // one Hessian-vector product fills the k-th column of all blocks [i][j]
+        for (j, (vj, shape_j)) in vars.iter().zip(shapes.iter()).enumerate() {
+            let n_j: usize = shape_j.iter().product();
+            for k in 0..n_j {
+                let columns = hvp_columns(&mut g, &grad, vars, *vj, unit_tensor(shape_j, k))?;
+                for (row, column) in blocks.iter_mut().zip(columns) {
+                    for (a, h) in column.iter().enumerate() {
+                        row[j][(a, k)] = *h;
+                    }
+                }
+            }
+        }
+        let blocks = blocks
+            .into_iter()
+            .zip(shapes.iter())
+            .map(|(row, shape_i)| {
+                row.into_iter()
+                    .zip(shapes.iter())
+                    .map(|(block, shape_j)| {
+                        let shape: Vec<usize> =
+                            shape_i.iter().chain(shape_j.iter()).cloned().collect();
+                        block.into_shape(IxDyn(&shape)).unwrap().into_shared()
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(blocks)
+    }
+
+    /// Copy of the graph with the gradient nodes of the scalar output
+    fn gradient_graph(&self, output: NodeIndex) -> Result<(Self, HashMap<NodeIndex, NodeIndex>)> {
+        let mut g = self.clone();
+        let ndim = g.eval_value(output)?.ndim();
+        if ndim != 0 {
+            return Err(Error::TensorRankMismatch {
+                actual: ndim,
+                desired: 0,
+            });
+        }
+        let grad = g.grad(output)?;
+        Ok((g, grad))
+    }
+
+    fn accumulate_adjoint(
+        &mut self,
+        adjoint: &mut HashMap<NodeIndex, NodeIndex>,
+        node: NodeIndex,
+        d: NodeIndex,
+    ) {
+        match adjoint.entry(node) {
+            Entry::Occupied(mut entry) => {
+                let sum = self.add(*entry.get(), d);
+                entry.insert(sum);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(d);
+            }
+        }
+    }
+
+    /// Nodes for `g * df/dx` where `y = f(x)`
+    fn unary_adjoint(&mut self, op: Unary, x: NodeIndex, y: NodeIndex, g: NodeIndex) -> NodeIndex {
+        match op {
+            Unary::Neg => self.neg(g),
+            Unary::Square => {
+                let two = self.constant_scalar(A::from_f64(2.0).unwrap());
+                let x2 = self.mul(x, two);
+                self.mul(g, x2)
+            }
+            Unary::Exp => self.mul(g, y),
+            Unary::Ln => self.div(g, x),
+            Unary::Sin => {
+                let c = self.cos(x);
+                self.mul(g, c)
+            }
+            Unary::Cos => {
+                let s = self.sin(x);
+                let gs = self.mul(g, s);
+                self.neg(gs)
+            }
+            Unary::Tan => {
+                let c = self.cos(x);
+                let cc = self.mul(c, c);
+                self.div(g, cc)
+            }
+            Unary::Sinh => {
+                let c = self.cosh(x);
+                self.mul(g, c)
+            }
+            Unary::Cosh => {
+                let s = self.sinh(x);
+                self.mul(g, s)
+            }
+            Unary::Tanh => {
+                let c = self.cosh(x);
+                let cc = self.mul(c, c);
+                self.div(g, cc)
+            }
+            Unary::StopGradient => unreachable!("StopGradient does not propagate adjoint"),
+        }
+    }
+
+    /// Nodes for `g * df/dlhs` and `g * df/drhs` where `y = f(lhs, rhs)`
+    fn binary_adjoint(
+        &mut self,
+        op: Binary,
+        lhs: NodeIndex,
+        rhs: NodeIndex,
+        g: NodeIndex,
+    ) -> (NodeIndex, NodeIndex) {
+        match op {
+            Binary::Add => (g, g),
+            Binary::Mul => (self.mul(g, rhs), self.mul(g, lhs)),
+            Binary::Div => {
+                let l = self.div(g, rhs);
+                let gl = self.mul(g, lhs);
+                let rr = self.mul(rhs, rhs);
+                let q = self.div(gl, rr);
+                (l, self.neg(q))
+            }
+            // `g` is a scalar, and is broadcast to the shape of the other argument
+            Binary::Dot => (self.mul(rhs, g), self.mul(lhs, g)),
+        }
+    }
+}
+
+/// Hessian-vector products `d/d(var) (d(output)/dw) . v` for all `ws`
+/// by a single forward sweep over the gradient graph
+fn hvp_columns<A: Scalar>(
+    g: &mut Graph<A>,
+    grad: &HashMap<NodeIndex, NodeIndex>,
+    ws: &[NodeIndex],
+    var: NodeIndex,
+    v: Tensor<A>,
+) -> Result<Vec<Tensor<A>>> {
+    let dws: Vec<NodeIndex> = ws.iter().filter_map(|w| grad.get(w).cloned()).collect();
+    let mut hvs = g.eval_jvp_many(&dws, &[(var, v)])?.into_iter();
+    ws.iter()
+        .map(|w| match grad.get(w) {
+            Some(_) => Ok(hvs.next().unwrap()),
+            None => Ok(Tensor::zeros(g.get_value(*w)?.shape())),
+        })
+        .collect()
+}
@@ -0,0 +1,379 @@
+//! Compiled execution plan of a graph
+//!
+//! `Graph::compile` resolves the ancestors of the outputs into a linear tape of
+//! instructions operating on fixed slots. Running the tape does not walk the graph,
+//! and the buffers of the slots allocated in the first run are reused afterwards.
+//! A slot is shared by intermediate values whose lifetimes do not overlap.
+//!
+//! ```
+//! use cagra::{graph::*, tensor::*};
+//!
+//! let mut g: Graph<f64> = Graph::new();
+//! let x = g.scalar("x", 2.0).unwrap();
+//! let y = g.square(x);
+//! let mut tape = g.compile(&[y]).unwrap();
+//!
+//! tape.set_value(x, 3.0.into_tensor()).unwrap();
+//! tape.forward().unwrap();
+//! tape.backward(y).unwrap();
+//! assert_eq!(tape.get_value(y).unwrap().as_scalar().unwrap(), 9.0);
+//! assert_eq!(tape.get_deriv(x).unwrap().as_scalar().unwrap(), 6.0);
+//! ```
+
+use cauchy::Scalar;
+use petgraph::prelude::*;
+use petgraph::visit::NodeIndexable;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::graph::{Graph, Property, Tensor};
+use crate::operator::{Binary, Operator, Unary};
+use crate::tensor::check_shape;
+
+/// Operands and results are referred by the positions of the nodes on the tape
+#[derive(Debug, Clone)]
+enum Instruction<A: Scalar> {
+    Unary {
+        op: Unary,
+        arg: usize,
+        out: usize,
+    },
+    Binary {
+        op: Binary,
+        lhs: usize,
+        rhs: usize,
+        out: usize,
+    },
+    Custom {
+        op: Arc<dyn Operator<A>>,
+        args: Vec<usize>,
+        out: usize,
+    },
+}
+
+impl<A: Scalar> Instruction<A> {
+    /// Position of the result
+    fn out(&self) -> usize {
+        match *self {
+            Instruction::Unary { out, .. }
+            | Instruction::Binary { out, .. }
+            | Instruction::Custom { out, .. } => out,
+        }
+    }
+
+    /// Positions of the arguments
+    fn args(&self) -> Vec<usize> {
+        match self {
+            Instruction::Unary { arg, .. } => vec![*arg],
+            Instruction::Binary { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Instruction::Custom { args, .. } => args.clone(),
+        }
+    }
+
+    /// Returns true if the derivative reads the values of the arguments
+    fn reads_args(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Unary {
+                op: Unary::Neg | Unary::StopGradient,
+                ..
+            } | Instruction::Binary {
+                op: Binary::Add,
+                ..
+            }
+        )
+    }
+}
+
+/// Linear instruction tape compiled from a `Graph`
+///
+/// Every node on which the outputs depend has a position on the tape, which keeps
+/// its derivative. Values are stored in slots assigned by a memory plan:
+/// an intermediate value releases its slot to later instructions after its last use,
+/// unless the backward pass reads it. Variables, constants and outputs keep their slots.
+#[derive(Debug, Clone)]
+pub struct Tape<A: Scalar> {
+    /// Position of each node on the tape indexed by the node index
+    positions: Vec<Option<usize>>,
+    nodes: Vec<NodeIndex>,
+    variables: Vec<bool>,
+    /// Value slot of each position
+    slots: Vec<usize>,
+    /// Whether the value of each position is kept after its last use
+    retained: Vec<bool>,
+    instructions: Vec<Instruction<A>>,
+    values: Vec<Option<Tensor<A>>>,
+    derivs: Vec<Option<Tensor<A>>>,
+}
+
+impl<A: Scalar> Graph<A> {
+    /// Compile the ancestors of the outputs into a `Tape`.
+    ///
+    /// The tape starts from the current values of the variables and constants,
+    /// and does not follow later changes of the graph.
+    pub fn compile(&self, outputs: &[NodeIndex]) -> Result<Tape<A>> {
+        let mut positions = vec![None; self.graph.node_bound()];
+        let mut nodes = Vec::new();
+        let mut variables = Vec::new();
+        let mut instructions = Vec::new();
+        let mut initial = Vec::new();
+        for &output in outputs {
+            for node in self.topological_order(output) {
+                if positions[node.index()].is_some() {
+                    continue;
+                }
+                let out = nodes.len();
+                let pos = |n: NodeIndex| positions[n.index()].unwrap();
+                let property = self[node].property;
+                match property {
+                    Property::Variable | Property::Constant => {
+                        initial.push((out, self.get_value(node).ok()));
+                    }
+                    Property::Unary(op) => {
+                        let arg = pos(self.get_arg1(node));
+                        instructions.push(Instruction::Unary { op, arg, out });
+                    }
+                    Property::Binary(op) => {
+                        let (lhs, rhs) = self.get_arg2(node);
+                        let (lhs, rhs) = (pos(lhs), pos(rhs));
+                        instructions.push(Instruction::Binary { op, lhs, rhs, out });
+                    }
+                    Property::Custom(id) => {
+                        let op = self.operators[id].clone();
+                        let args = self.args(node).iter().map(|&arg| pos(arg)).collect();
+                        instructions.push(Instruction::Custom { op, args, out });
+                    }
+                }
+                variables.push(self[node].is_variable());
+                nodes.push(node);
+                positions[node.index()] = Some(out);
+            }
+        }
+
+        // the last instruction using each value, where `None` keeps the value
+        let mut last_use: Vec<Option<usize>> = (0..nodes.len()).map(Some).collect();
+        for &(pos, _) in &initial {
+            last_use[pos] = None;
+        }
+        for &output in outputs {
+            last_use[positions[output.index()].unwrap()] = None;
+        }
+        for inst in &instructions {
+            for arg in inst.args() {
+                if inst.reads_args() {
+                    last_use[arg] = None;
+                } else if let Some(last) = last_use[arg].as_mut() {
+                    *last = inst.out();
+                }
+            }
+        }
+
+        let mut slots = vec![0; nodes.len()];
+        let mut values = Vec::new();
+        let mut released = Vec::new();
+        for &(pos, ref value) in &initial {
+            slots[pos] = values.len();
+            values.push(value.clone());
+        }
+        for inst in &instructions {
+            let out = inst.out();
+            slots[out] = released.pop().unwrap_or_else(|| {
+                values.push(None);
+                values.len() - 1
+            });
+            let mut args = inst.args();
+            args.dedup();
+            for arg in args {
+                if last_use[arg] == Some(out) {
+                    released.push(slots[arg]);
+                }
+            }
+        }
+        let retained = last_use.iter().map(Option::is_none).collect();
+        let derivs = vec![None; nodes.len()];
+        Ok(Tape {
+            positions,
+            nodes,
+            variables,
+            slots,
+            retained,
+            instructions,
+            values,
+            derivs,
+        })
+    }
+}
+
+impl<A: Scalar> Tape<A> {
+    fn position(&self, node: NodeIndex) -> Result<usize> {
+        self.positions
+            .get(node.index())
+            .cloned()
+            .and_then(|pos| pos)
+            .ok_or(Error::NodeNotCompiled {
+                index: node.index(),
+            })
+    }
+
+    fn value<'a>(
+        values: &'a [Option<Tensor<A>>],
+        slots: &[usize],
+        nodes: &[NodeIndex],
+        pos: usize,
+    ) -> Result<&'a Tensor<A>> {
+        values[slots[pos]]
+            .as_ref()
+            .ok_or_else(|| Error::ValueUninitialized {
+                index: nodes[pos].index(),
+            })
+    }
+
+    /// Number of the value slots allocated by the memory plan
+    pub fn num_slots(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Set a value to a variable, and returns `NodeTypeError` if the node is not a variable
+    pub fn set_value(&mut self, node: NodeIndex, value: Tensor<A>) -> Result<()> {
+        let pos = self.position(node)?;
+        if !self.variables[pos] {
+            return Err(Error::NodeTypeError {
+                index: node.index(),
+            });
+        }
+        self.values[self.slots[pos]] = Some(value);
+        Ok(())
+    }
+
+    /// Get the value of the node.
+    ///
+    /// Returns `ValueReleased` if the slot of the value is reused by later instructions.
+    pub fn get_value(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        let pos = self.position(node)?;
+        if !self.retained[pos] {
+            return Err(Error::ValueReleased {
+                index: node.index(),
+            });
+        }
+        Ok(Self::value(&self.values, &self.slots, &self.nodes, pos)?.clone())
+    }
+
+    pub fn get_deriv(&self, node: NodeIndex) -> Result<Tensor<A>> {
+        let pos = self.position(node)?;
+        self.derivs[pos].clone().ok_or(Error::DerivUninitialized {
+            index: node.index(),
+        })
+    }
+
+    /// Evaluate the values of all nodes on the tape
+    pub fn forward(&mut self) -> Result<()> {
+        let nodes = &self.nodes;
+        let slots = &self.slots;
+        let values = &mut self.values;
+        for inst in &self.instructions {
+            // the slot of the result never holds the arguments
+            let out = slots[inst.out()];
+            let mut buf = values[out].take();
+            match inst {
+                Instruction::Unary { op, arg, .. } => {
+                    let arg = Self::value(values, slots, nodes, *arg)?;
+                    match &mut buf {
+                        Some(buf) if buf.shape() == arg.shape() => op.eval_value_into(arg, buf),
+                        _ => buf = Some(op.eval_value(arg.clone())),
+                    }
+                }
+                Instruction::Binary { op, lhs, rhs, .. } => {
+                    let lhs = Self::value(values, slots, nodes, *lhs)?;
+                    let rhs = Self::value(values, slots, nodes, *rhs)?;
+                    match &mut buf {
+                        Some(buf) => op.eval_value_into(lhs, rhs, buf),
+                        None => buf = Some(op.eval_value(lhs.clone(), rhs.clone())),
+                    }
+                }
+                Instruction::Custom { op, args, .. } => {
+                    let args = args
+                        .iter()
+                        .map(|&pos| Self::value(values, slots, nodes, pos).cloned())
+                        .collect::<Result<Vec<_>>>()?;
+                    buf = Some(op.eval_value(&args));
+                }
+            }
+            values[out] = buf;
+        }
+        Ok(())
+    }
+
+    /// Evaluate derivative of the node with respect to the nodes on the tape.
+    /// `forward` must be called before.
+    pub fn backward(&mut self, node: NodeIndex) -> Result<()> {
+        let value = self.get_value(node)?;
+        self.backward_with_seed(node, Tensor::ones(value.shape()))
+    }
+
+    /// Evaluate derivative of the node seeded by the given derivative of the node.
+    ///
+    /// Returns `TensorShapeMismatch` if the shape of the seed differs from the value.
+    pub fn backward_with_seed(&mut self, node: NodeIndex, seed: Tensor<A>) -> Result<()> {
+        let pos = self.position(node)?;
+        check_shape(&seed, self.get_value(node)?.shape())?;
+        for der in self.derivs.iter_mut() {
+            *der = None;
+        }
+        self.derivs[pos] = Some(seed);
+
+        let values = &self.values;
+        let slots = &self.slots;
+        let nodes = &self.nodes;
+        let derivs = &mut self.derivs;
+        // the slots of released arguments are only passed to the operators
+        // whose derivative does not read them
+        let value = |pos: usize| Self::value(values, slots, nodes, pos).cloned();
+        for inst in self.instructions.iter().rev() {
+            match inst {
+                Instruction::Unary {
+                    op: Unary::StopGradient,
+                    ..
+                } => {}
+                Instruction::Unary { op, arg, out } => {
+                    let der = match &derivs[*out] {
+                        Some(der) => der.clone(),
+                        None => continue,
+                    };
+                    let der = op.eval_deriv(value(*arg)?, der);
+                    accumulate(&mut derivs[*arg], der);
+                }
+                Instruction::Binary { op, lhs, rhs, out } => {
+                    let der = match &derivs[*out] {
+                        Some(der) => der.clone(),
+                        None => continue,
+                    };
+                    let (l_der, r_der) = op.eval_deriv(value(*lhs)?, value(*rhs)?, der);
+                    accumulate(&mut derivs[*lhs], l_der);
+                    accumulate(&mut derivs[*rhs], r_der);
+                }
+                Instruction::Custom { op, args, out } => {
+                    let der = match &derivs[*out] {
+                        Some(der) => der.clone(),
+                        None => continue,
+                    };
+                    let args_values = args
+                        .iter()
+                        .map(|&pos| value(pos))
+                        .collect::<Result<Vec<_>>>()?;
+                    for (&arg, der) in args.iter().zip(op.eval_deriv(&args_values, der)) {
+                        accumulate(&mut derivs[arg], der);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Add the derivative into the slot in place
+fn accumulate<A: Scalar>(slot: &mut Option<Tensor<A>>, der: Tensor<A>) {
+    match slot {
+        Some(last) => *last += &der,
+        None => *slot = Some(der),
+    }
+}
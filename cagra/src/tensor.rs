@@ -14,7 +14,7 @@ impl<A: Scalar> IntoTensor<A> for A {
     }
 }
 
-impl<'a, A: Scalar> IntoTensor<A> for &'a [A] {
+impl<A: Scalar> IntoTensor<A> for &[A] {
     fn into_tensor(self) -> Tensor<A> {
         arr1(self).into_dyn().into_shared()
     }
@@ -47,3 +47,21 @@ impl<A: Scalar> TensorCast<A> for Tensor<A> {
         Ok(self.as_slice().unwrap())
     }
 }
+
+/// Tensor whose `k`-th element in the logical order is one and others are zero
+pub(crate) fn unit_tensor<A: Scalar>(shape: &[usize], k: usize) -> Tensor<A> {
+    let mut e = Array::zeros(IxDyn(shape));
+    e.as_slice_mut().unwrap()[k] = A::one();
+    e.into_shared()
+}
+
+/// Returns `TensorShapeMismatch` unless the tensor has the desired shape
+pub(crate) fn check_shape<A: Scalar>(tensor: &Tensor<A>, desired: &[usize]) -> Result<()> {
+    if tensor.shape() != desired {
+        return Err(Error::TensorShapeMismatch {
+            actual: tensor.shape().to_vec(),
+            desired: desired.to_vec(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+#[test]
+fn test_accumulate() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.square(x);
+    let z = g.sin(y);
+    g.set_accumulate(true);
+    assert!(g.is_accumulating());
+
+    let mut expected = 0.0;
+    for &v in &[1.0, 2.0, 3.0] {
+        g.set_value(x, v.into_tensor())?;
+        g.eval_value(z)?;
+        g.eval_deriv(z)?;
+        expected += (v * v).cos() * 2.0 * v;
+        // derivatives of the previous passes are not propagated again
+        assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, expected, epsilon = 1e-12);
+    }
+
+    g.zero_grad();
+    assert!(g.get_deriv(x).is_err());
+    g.eval_deriv(z)?;
+    assert_abs_diff_eq!(
+        g.get_deriv(x)?.as_scalar()?,
+        9.0f64.cos() * 6.0,
+        epsilon = 1e-12
+    );
+    Ok(())
+}
+
+#[test]
+fn test_accumulate_disabled() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.square(x);
+    g.eval_value(y)?;
+    g.eval_deriv(y)?;
+    g.eval_deriv(y)?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 4.0);
+    Ok(())
+}
+
+#[test]
+fn test_accumulate_many() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.square(x);
+    let z = g.mul(y, x);
+    g.eval_value(z)?;
+    g.set_accumulate(true);
+    g.eval_deriv(y)?;
+    g.eval_deriv_many(&[(z, 1.0.into_tensor())])?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 16.0);
+    assert_eq!(g.get_deriv(y)?.as_scalar()?, 3.0);
+    Ok(())
+}
+
+#[test]
+fn test_accumulate_jacobian() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0, 3.0])?;
+    let w = g.vector("w", &[1.0, 1.0, 1.0])?;
+    let y = g.mul(x, w);
+    let s = g.dot(y, w);
+    g.set_accumulate(true);
+    g.eval_value(s)?;
+    g.eval_deriv(s)?;
+
+    // reverse sweeps of the Jacobian do not accumulate over its rows
+    let jac = g.jacobian(y, &[x, w])?;
+    let identity: &[f64] = &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+    assert_eq!(jac[0].iter().cloned().collect::<Vec<_>>(), identity);
+    let diag_x: &[f64] = &[1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0];
+    assert_eq!(jac[1].iter().cloned().collect::<Vec<_>>(), diag_x);
+
+    // and leave the accumulated gradients as they are
+    assert_eq!(g.get_deriv(x)?.as_vector()?, &[1.0, 1.0, 1.0]);
+    g.eval_deriv(s)?;
+    assert_eq!(g.get_deriv(x)?.as_vector()?, &[2.0, 2.0, 2.0]);
+    Ok(())
+}
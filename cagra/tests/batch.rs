@@ -0,0 +1,74 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::*, graph::Graph, tensor::*};
+use ndarray::*;
+
+// f(x; w) = w . sin(x) + c * exp(x_0)
+fn graph() -> Result<(Graph<f64>, [petgraph::graph::NodeIndex; 3])> {
+    let mut g = Graph::new();
+    let x = g.vector("x", &[0.0, 0.0])?;
+    let w = g.vector("w", &[0.5, -1.5])?;
+    let s = g.sin(x);
+    let d = g.dot(w, s);
+    let c = g.constant_vector(&[2.0, 2.0]);
+    let e = g.exp(x);
+    let ce = g.mul(e, c);
+    let f = g.dot(ce, w);
+    let f = g.add(f, d);
+    Ok((g, [x, w, f]))
+}
+
+#[test]
+fn test_batch_matches_samples() -> Result<()> {
+    let (mut g, [x, w, f]) = graph()?;
+    let xs = arr2(&[[0.1, 0.2], [0.3, -0.4], [1.0, 2.0]])
+        .into_dyn()
+        .into_shared();
+    let values = g.eval_value_batch(f, &[(x, xs.clone())])?;
+    let derivs = g.eval_deriv_batch(f, &[(x, xs.clone())])?;
+    assert_eq!(values.shape(), &[3]);
+    assert_eq!(derivs[&x].shape(), &[3, 2]);
+    assert_eq!(derivs[&w].shape(), &[3, 2]);
+
+    for i in 0..3 {
+        let xi = xs.index_axis(Axis(0), i).to_owned().into_shared();
+        g.set_value(x, xi)?;
+        let value = g.eval_value(f)?.as_scalar()?;
+        g.eval_deriv(f)?;
+        assert_abs_diff_eq!(values[[i]], value, epsilon = 1e-12);
+        for &var in &[x, w] {
+            let expected = g.get_deriv(var)?;
+            let actual = derivs[&var].index_axis(Axis(0), i);
+            for (a, b) in actual.iter().zip(expected.iter()) {
+                assert_abs_diff_eq!(a, b, epsilon = 1e-12);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_batch_scalar_broadcast() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let a = g.scalar("a", 1.0)?;
+    let y = g.mul(x, a);
+    let z = g.dot(y, y);
+    let a_s = arr1(&[1.0, 2.0, 3.0]).into_dyn().into_shared();
+    let derivs = g.eval_deriv_batch(z, &[(a, a_s)])?;
+    // z = a^2 |x|^2, dz/da = 2 a |x|^2
+    assert_eq!(derivs[&a].as_vector()?, &[10.0, 20.0, 30.0]);
+    assert_eq!(derivs[&x].shape(), &[3, 2]);
+    Ok(())
+}
+
+#[test]
+fn test_batch_size_mismatch() -> Result<()> {
+    let (g, [x, w, f]) = graph()?;
+    let xs = Array2::zeros((3, 2)).into_dyn().into_shared();
+    let ws = Array2::zeros((4, 2)).into_dyn().into_shared();
+    match g.eval_value_batch(f, &[(x, xs), (w, ws)]) {
+        Err(Error::TensorShapeMismatch { .. }) => {}
+        _ => panic!("Batch size mismatch is not detected"),
+    }
+    Ok(())
+}
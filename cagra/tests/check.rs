@@ -0,0 +1,181 @@
+use approx::assert_abs_diff_eq;
+use cagra::{
+    check::*,
+    error::{Error, Result},
+    graph::Graph,
+    operator::Operator,
+    tensor::*,
+};
+
+const X0: &[f64] = &[0.3, 0.7, 1.2];
+const Y0: &[f64] = &[1.1, -0.4, 0.9];
+
+/// Check the derivative of a unary operator by both of the finite differences
+macro_rules! check_unary {
+    ($test:ident, $op:ident) => {
+        #[test]
+        fn $test() -> Result<()> {
+            let mut g: Graph<f64> = Graph::new();
+            let x = g.vector("x", X0)?;
+            let y = g.$op(x);
+            let central = check_central(&g, y, 1e-6)?;
+            assert!(central.is_within(1e-7), "{:?}", central);
+            let complex = check_complex_step(&g, y, 1e-20)?;
+            assert!(complex.is_within(1e-12), "{:?}", complex);
+            Ok(())
+        }
+    };
+}
+
+/// Check the derivatives of a binary operator by both of the finite differences
+macro_rules! check_binary {
+    ($test:ident, $op:ident) => {
+        #[test]
+        fn $test() -> Result<()> {
+            let mut g: Graph<f64> = Graph::new();
+            let x = g.vector("x", X0)?;
+            let y = g.vector("y", Y0)?;
+            let z = g.$op(x, y);
+            let central = check_central(&g, z, 1e-6)?;
+            assert!(central.is_within(1e-7), "{:?}", central);
+            let complex = check_complex_step(&g, z, 1e-20)?;
+            assert!(complex.is_within(1e-12), "{:?}", complex);
+            Ok(())
+        }
+    };
+}
+
+check_unary!(test_neg, neg);
+check_unary!(test_square, square);
+check_unary!(test_exp, exp);
+check_unary!(test_ln, ln);
+check_unary!(test_sin, sin);
+check_unary!(test_cos, cos);
+check_unary!(test_tan, tan);
+check_unary!(test_sinh, sinh);
+check_unary!(test_cosh, cosh);
+check_unary!(test_tanh, tanh);
+
+check_binary!(test_add, add);
+check_binary!(test_sub, sub);
+check_binary!(test_mul, mul);
+check_binary!(test_div, div);
+check_binary!(test_dot, dot);
+
+#[test]
+fn test_composite() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", X0)?;
+    let y = g.vector("y", Y0)?;
+    let t = g.tan(x);
+    let s = g.mul(t, y);
+    let e = g.exp(s);
+    let z = g.dot(e, x);
+    let report = check_complex_step(&g, z, 1e-20)?;
+    assert!(report.is_within(1e-12), "{:?}", report);
+    assert_eq!(report.variables.len(), 2);
+    assert_eq!(report.variables[0].name.as_ref().unwrap(), "x");
+    assert_eq!(report.variables[1].name.as_ref().unwrap(), "y");
+    Ok(())
+}
+
+#[test]
+fn test_stop_gradient_detected() -> Result<()> {
+    // the derivative through `stop_gradient` is intentionally cut off
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", X0)?;
+    let s = g.stop_gradient(x);
+    let y = g.mul(s, x);
+    let report = check_complex_step(&g, y, 1e-20)?;
+    assert!(!report.is_within(1e-6));
+    assert!(report.max_abs_error() > 0.2);
+    Ok(())
+}
+
+/// `sin(x)` with the derivative of `cos(x)` flipped
+#[derive(Debug)]
+struct WrongSin;
+
+impl Operator<f64> for WrongSin {
+    fn name(&self) -> String {
+        "wrong_sin".into()
+    }
+
+    fn eval_value(&self, args: &[Tensor<f64>]) -> Tensor<f64> {
+        args[0].mapv(f64::sin).into_shared()
+    }
+
+    fn eval_deriv(&self, args: &[Tensor<f64>], deriv: Tensor<f64>) -> Vec<Tensor<f64>> {
+        vec![(&deriv * &args[0].mapv(|x| -x.cos())).into_shared()]
+    }
+
+    fn eval_jvp(&self, args: &[Tensor<f64>], tangents: &[Tensor<f64>]) -> Tensor<f64> {
+        (&tangents[0] * &args[0].mapv(|x| -x.cos())).into_shared()
+    }
+}
+
+#[test]
+fn test_custom() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", X0)?;
+    let y = g.custom(Box::new(WrongSin), &[x]);
+    let report = check_central(&g, y, 1e-6)?;
+    assert!(!report.is_within(1e-3));
+    assert_abs_diff_eq!(report.variables[0].rel_error, 2.0, epsilon = 1e-6);
+    match check_complex_step(&g, y, 1e-20) {
+        Err(Error::UnsupportedOperator { index }) => assert_eq!(index, y.index()),
+        _ => panic!("Custom operator is evaluated with complex values"),
+    }
+    Ok(())
+}
+
+/// `sin(x)` with the derivative of NaN
+#[derive(Debug)]
+struct NanSin;
+
+impl Operator<f64> for NanSin {
+    fn name(&self) -> String {
+        "nan_sin".into()
+    }
+
+    fn eval_value(&self, args: &[Tensor<f64>]) -> Tensor<f64> {
+        args[0].mapv(f64::sin).into_shared()
+    }
+
+    fn eval_deriv(&self, args: &[Tensor<f64>], _deriv: Tensor<f64>) -> Vec<Tensor<f64>> {
+        vec![args[0].mapv(|_| f64::NAN).into_shared()]
+    }
+
+    fn eval_jvp(&self, args: &[Tensor<f64>], _tangents: &[Tensor<f64>]) -> Tensor<f64> {
+        args[0].mapv(|_| f64::NAN).into_shared()
+    }
+}
+
+#[test]
+fn test_nan_deriv() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", X0)?;
+    let y = g.custom(Box::new(NanSin), &[x]);
+    let report = check_central(&g, y, 1e-6)?;
+    assert!(!report.is_within(1e-3));
+    assert_eq!(report.max_abs_error(), f64::INFINITY);
+    assert_eq!(report.max_rel_error(), f64::INFINITY);
+    Ok(())
+}
+
+#[test]
+fn test_accumulated_graph() -> Result<()> {
+    // derivatives accumulated in the graph do not leak into the check
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", X0)?;
+    let y = g.sin(x);
+    g.set_accumulate(true);
+    g.eval_value(y)?;
+    g.eval_deriv(y)?;
+    g.eval_deriv(y)?;
+    let accumulated = g.get_deriv(x)?;
+    let report = check_complex_step(&g, y, 1e-20)?;
+    assert!(report.is_within(1e-12), "{:?}", report);
+    assert_eq!(g.get_deriv(x)?, accumulated);
+    Ok(())
+}
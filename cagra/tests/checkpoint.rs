@@ -0,0 +1,103 @@
+use cagra::{error::Result, graph::Graph};
+use petgraph::graph::NodeIndex;
+
+const DEPTH: usize = 64;
+
+// y = sum(sin(...sin(x * w)...))
+fn graph() -> Result<(Graph<f64>, NodeIndex, NodeIndex, Vec<NodeIndex>)> {
+    let mut g = Graph::new();
+    let x = g.vector("x", &[0.1, 0.2, 0.3, 0.4])?;
+    let w = g.vector("w", &[1.0, -1.0, 0.5, 2.0])?;
+    let mut h = g.mul(x, w);
+    let mut layers = vec![h];
+    for _ in 0..DEPTH {
+        h = g.sin(h);
+        layers.push(h);
+    }
+    let one = g.constant_vector(&[1.0; 4]);
+    let y = g.dot(h, one);
+    Ok((g, x, y, layers))
+}
+
+#[test]
+fn test_checkpointed_matches_plain() -> Result<()> {
+    let (mut g, x, y, layers) = graph()?;
+    let value = g.eval_value(y)?;
+    g.eval_deriv(y)?;
+    let deriv = g.get_deriv(x)?;
+
+    let (mut h, x, y, _) = graph()?;
+    h.mark_checkpoint(layers[DEPTH / 2]);
+    assert_eq!(h.eval_value_checkpointed(y)?, value);
+    assert!(h.get_value(layers[DEPTH / 2]).is_ok());
+    assert!(h.get_value(layers[1]).is_err());
+    assert!(h.get_value(layers[DEPTH]).is_err());
+
+    h.eval_deriv_checkpointed(y)?;
+    assert_eq!(h.get_deriv(x)?, deriv);
+    assert!(h.get_value(layers[1]).is_err());
+    // dropped values are recomputed
+    assert_eq!(h.eval_value(layers[DEPTH])?, g.get_value(layers[DEPTH])?);
+    Ok(())
+}
+
+#[test]
+fn test_choose_checkpoints() -> Result<()> {
+    let (mut g, x, y, _) = graph()?;
+    g.eval_value(y)?;
+    g.eval_deriv(y)?;
+    let deriv = g.get_deriv(x)?;
+
+    let budget = 4 * 8;
+    let (mut h, x, y, layers) = graph()?;
+    let checkpoints = h.choose_checkpoints(y, budget)?;
+    assert!(!checkpoints.is_empty());
+    assert!(checkpoints.len() * 4 <= budget);
+    assert!(layers[..DEPTH / 2].iter().any(|n| checkpoints.contains(n)));
+    assert!(layers[DEPTH / 2..].iter().any(|n| checkpoints.contains(n)));
+    assert!(checkpoints.iter().all(|&n| h.get_value(n).is_ok()));
+
+    h.eval_deriv_checkpointed(y)?;
+    assert_eq!(h.get_deriv(x)?, deriv);
+    let kept = layers.iter().filter(|&&n| h.get_value(n).is_ok()).count();
+    assert_eq!(kept, checkpoints.len());
+
+    // everything fits in a large budget
+    assert_eq!(h.choose_checkpoints(y, 1 << 20)?.len(), layers.len());
+    assert!(layers.iter().all(|&n| h.get_value(n).is_ok()));
+    h.clear_checkpoints();
+    h.set_value(x, g.get_value(x)?)?;
+    h.eval_value_checkpointed(y)?;
+    assert!(layers.iter().all(|&n| h.get_value(n).is_err()));
+    Ok(())
+}
+
+#[test]
+fn test_choose_checkpoints_mixed_sizes() -> Result<()> {
+    // y = (v * x^2) . (v * x^2) with the intermediate values of 1 and 10 elements
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let v = g.vector("v", &[1.0; 10])?;
+    let a = g.square(x);
+    let b = g.mul(v, a);
+    let y = g.dot(b, b);
+    assert_eq!(g.choose_checkpoints(y, 11)?, vec![a, b]);
+    assert_eq!(g.choose_checkpoints(y, 10)?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_checkpointed_drops_cached_values() -> Result<()> {
+    let (mut g, _, y, layers) = graph()?;
+    g.eval_value(y)?;
+    assert!(layers.iter().all(|&n| g.get_value(n).is_ok()));
+    g.mark_checkpoint(layers[DEPTH / 2]);
+    g.eval_value_checkpointed(y)?;
+    let kept: Vec<NodeIndex> = layers
+        .iter()
+        .cloned()
+        .filter(|&n| g.get_value(n).is_ok())
+        .collect();
+    assert_eq!(kept, vec![layers[DEPTH / 2]]);
+    Ok(())
+}
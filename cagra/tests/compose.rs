@@ -0,0 +1,73 @@
+use cagra::{
+    error::{Error, Result},
+    graph::Graph,
+    tensor::*,
+};
+
+/// V(q) = q^2 / 2
+fn potential() -> Result<Graph<f64>> {
+    let mut g = Graph::new();
+    let q = g.scalar("q", 0.0)?;
+    let k = g.scalar("k", 1.0)?;
+    let half = g.constant_scalar(0.5);
+    let q2 = g.square(q);
+    let kq2 = g.mul(k, q2);
+    let v = g.mul(half, kq2);
+    g.set_name(v, "V");
+    Ok(g)
+}
+
+#[test]
+fn test_inline() -> Result<()> {
+    let pot = potential()?;
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+
+    let vx = g.inline(&pot, "vx.", &[("q", x)])?;
+    let vy = g.inline(&pot, "vy.", &[("q", y)])?;
+    assert_eq!(vx["q"], x);
+    assert_eq!(g.get_index("vx.V"), vx["V"]);
+    assert_eq!(g.get_index("vy.k"), vy["k"]);
+    assert_ne!(vx["k"], vy["k"]);
+
+    let e = g.add(vx["V"], vy["V"]);
+    assert_eq!(g.eval_value(e)?.as_scalar()?, 2.0 + 4.5);
+    g.set_value(vy["k"], 2.0.into_tensor())?;
+    assert_eq!(g.eval_value(e)?.as_scalar()?, 2.0 + 9.0);
+    g.eval_deriv(e)?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 2.0);
+    assert_eq!(g.get_deriv(y)?.as_scalar()?, 6.0);
+    Ok(())
+}
+
+#[test]
+fn test_inline_errors() -> Result<()> {
+    let pot = potential()?;
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    g.inline(&pot, "p.", &[("q", x)])?;
+    let n = g.variables().len();
+
+    match g.inline(&pot, "p.", &[("q", x)]) {
+        Err(Error::DuplicatedName { name }) => assert!(name.starts_with("p.")),
+        _ => panic!("Duplicated prefix must be rejected"),
+    }
+    match g.inline(&pot, "r.", &[("p", x)]) {
+        Err(Error::UndefinedName { name }) => assert_eq!(name, "p"),
+        _ => panic!("Unknown input must be rejected"),
+    }
+    match g.inline(&pot, "r.", &[("V", x)]) {
+        Err(Error::NodeTypeError { .. }) => {}
+        _ => panic!("Non-variable input must be rejected"),
+    }
+    let stale = g.scalar("stale", 1.0)?;
+    g.remove_node(stale)?;
+    match g.inline(&pot, "r.", &[("q", stale)]) {
+        Err(Error::NodeNotFound { index }) => assert_eq!(index, stale.index()),
+        _ => panic!("Removed node must be rejected"),
+    }
+    // failed calls do not change the graph
+    assert_eq!(g.variables().len(), n);
+    Ok(())
+}
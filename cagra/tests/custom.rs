@@ -0,0 +1,150 @@
+use approx::assert_abs_diff_eq;
+use cagra::{
+    error::{Error, Result},
+    graph::Graph,
+    operator::Operator,
+    serialize::Registry,
+    tensor::*,
+};
+use ndarray::arr1;
+
+/// `a * b + c`
+#[derive(Debug)]
+struct Fma;
+
+impl Operator<f64> for Fma {
+    fn name(&self) -> String {
+        "fma".into()
+    }
+
+    fn eval_value(&self, args: &[Tensor<f64>]) -> Tensor<f64> {
+        (&args[0] * &args[1] + &args[2]).into_shared()
+    }
+
+    fn eval_deriv(&self, args: &[Tensor<f64>], deriv: Tensor<f64>) -> Vec<Tensor<f64>> {
+        vec![
+            (&deriv * &args[1]).into_shared(),
+            (&deriv * &args[0]).into_shared(),
+            deriv,
+        ]
+    }
+
+    fn eval_jvp(&self, args: &[Tensor<f64>], tangents: &[Tensor<f64>]) -> Tensor<f64> {
+        (&tangents[0] * &args[1] + &args[0] * &tangents[1] + &tangents[2]).into_shared()
+    }
+}
+
+/// `factor * x`
+#[derive(Debug)]
+struct Scale {
+    factor: f64,
+}
+
+impl Operator<f64> for Scale {
+    fn name(&self) -> String {
+        "scale".into()
+    }
+
+    fn params(&self) -> serde_json::Value {
+        self.factor.into()
+    }
+
+    fn eval_value(&self, args: &[Tensor<f64>]) -> Tensor<f64> {
+        args[0].mapv(|x| self.factor * x).into_shared()
+    }
+
+    fn eval_deriv(&self, _args: &[Tensor<f64>], deriv: Tensor<f64>) -> Vec<Tensor<f64>> {
+        vec![deriv.mapv(|d| self.factor * d).into_shared()]
+    }
+
+    fn eval_jvp(&self, _args: &[Tensor<f64>], tangents: &[Tensor<f64>]) -> Tensor<f64> {
+        tangents[0].mapv(|t| self.factor * t).into_shared()
+    }
+}
+
+fn registry() -> Result<Registry<f64>> {
+    let mut registry = Registry::new();
+    registry.register("fma", |_| Ok(Box::new(Fma)))?;
+    registry.register("scale", |params| {
+        Ok(Box::new(Scale {
+            factor: params.as_f64().unwrap(),
+        }))
+    })?;
+    Ok(registry)
+}
+
+// f = 3 * fma(x, y, sin(x))
+fn graph() -> Result<Graph<f64>> {
+    let mut g = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 5.0)?;
+    let s = g.sin(x);
+    let a = g.custom(Box::new(Fma), &[x, y, s]);
+    let f = g.custom(Box::new(Scale { factor: 3.0 }), &[a]);
+    g.set_name(f, "f");
+    Ok(g)
+}
+
+#[test]
+fn test_custom_operator() -> Result<()> {
+    let mut g = graph()?;
+    let (x, y, f) = (g.get_index("x"), g.get_index("y"), g.get_index("f"));
+    assert_abs_diff_eq!(
+        g.eval_value(f)?.as_scalar()?,
+        3.0 * (10.0 + 2.0f64.sin()),
+        epsilon = 1e-12
+    );
+
+    let dx = 3.0 * (5.0 + 2.0f64.cos());
+    g.eval_deriv(f)?;
+    assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, dx, epsilon = 1e-12);
+    assert_abs_diff_eq!(g.get_deriv(y)?.as_scalar()?, 6.0, epsilon = 1e-12);
+
+    let jvp = g.eval_jvp(f, &[(x, 1.0.into_tensor())])?;
+    assert_abs_diff_eq!(jvp.as_scalar()?, dx, epsilon = 1e-12);
+
+    let mut tape = g.compile(&[f])?;
+    tape.forward()?;
+    tape.backward(f)?;
+    assert_abs_diff_eq!(tape.get_deriv(x)?.as_scalar()?, dx, epsilon = 1e-12);
+
+    let xs = arr1(&[2.0, 0.0]).into_dyn().into_shared();
+    let values = g.eval_value_batch(f, &[(x, xs.clone())])?;
+    assert_abs_diff_eq!(values[0], 3.0 * (10.0 + 2.0f64.sin()), epsilon = 1e-12);
+    assert_abs_diff_eq!(values[1], 0.0, epsilon = 1e-12);
+    let derivs = g.eval_deriv_batch(f, &[(x, xs)])?;
+    assert_abs_diff_eq!(derivs[&x][0], dx, epsilon = 1e-12);
+    assert_abs_diff_eq!(derivs[&x][1], 3.0 * (5.0 + 1.0), epsilon = 1e-12);
+
+    match g.grad(f) {
+        Err(Error::UnsupportedOperator { .. }) => {}
+        _ => panic!("Custom operators have no symbolic derivative"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_serialize_custom_operator() -> Result<()> {
+    let mut g = graph()?;
+    let json = g.to_json()?;
+    let mut h = Graph::from_json(&json, &registry()?)?;
+    let f = h.get_index("f");
+    assert_eq!(h.eval_value(f)?, g.eval_value(g.get_index("f"))?);
+    h.set_value(h.get_index("y"), 1.0.into_tensor())?;
+    assert_abs_diff_eq!(
+        h.eval_value(f)?.as_scalar()?,
+        3.0 * (2.0 + 2.0f64.sin()),
+        epsilon = 1e-12
+    );
+
+    match Graph::<f64>::from_json(&json, &Registry::new()) {
+        Err(Error::UnregisteredOperator { name }) => assert_eq!(name, "fma"),
+        _ => panic!("Unregistered operator must be rejected"),
+    }
+    let mut registry = registry()?;
+    match registry.register("fma", |_| Ok(Box::new(Fma))) {
+        Err(Error::DuplicatedName { .. }) => {}
+        _ => panic!("Duplicated operator must be rejected"),
+    }
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+// x = x + 1 + 1 + ... deep enough to overflow the stack if evaluated recursively
+#[test]
+fn test_deep_chain() -> Result<()> {
+    let n = 200_000;
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.0)?;
+    let mut y = x;
+    for _ in 0..n {
+        let v = g.constant_scalar(1.0);
+        y = g.add(y, v);
+    }
+    assert_eq!(g.eval_value(y)?.as_scalar()?, n as f64);
+    g.eval_deriv(y)?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 1.0);
+    Ok(())
+}
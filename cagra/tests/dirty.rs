@@ -0,0 +1,36 @@
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+#[test]
+fn test_set_value_invalidates_downstream() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.scalar("y", 2.0)?;
+    let sx = g.square(x);
+    let sy = g.square(y);
+    let z = g.add(sx, sy);
+    assert_eq!(g.eval_value(z)?.as_scalar()?, 5.0);
+
+    g.set_value(x, 3.0.into_tensor())?;
+    // downstream of x is dirty
+    assert!(g.get_value(sx).is_err());
+    assert!(g.get_value(z).is_err());
+    // unrelated branch keeps its cache
+    assert_eq!(g.get_value(sy)?.as_scalar()?, 4.0);
+
+    assert_eq!(g.eval_value(z)?.as_scalar()?, 13.0);
+    g.eval_deriv(z)?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 6.0);
+    assert_eq!(g.get_deriv(y)?.as_scalar()?, 4.0);
+    Ok(())
+}
+
+#[test]
+fn test_eval_deriv_dirty() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.exp(x);
+    g.eval_value(y)?;
+    g.set_value(x, 2.0.into_tensor())?;
+    assert!(g.eval_deriv(y).is_err());
+    Ok(())
+}
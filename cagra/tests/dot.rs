@@ -1,4 +1,4 @@
-use approx::abs_diff_eq;
+use approx::assert_abs_diff_eq;
 use cagra::{error::Result, graph::Graph, tensor::*};
 
 #[test]
@@ -9,9 +9,11 @@ fn test_dot() -> Result<()> {
     let x = g.vector("x", x0)?;
     let y = g.vector("y", y0)?;
     let z = g.dot(x, y);
-    abs_diff_eq!(g.eval_value(z)?.as_scalar()?, 1.0 * 3.0 + 2.0 * 4.0);
+    assert_abs_diff_eq!(g.eval_value(z)?.as_scalar()?, 1.0 * 3.0 + 2.0 * 4.0);
     g.eval_deriv(z)?;
-    abs_diff_eq!(g.get_deriv(x)?.as_vector()?, y0);
-    abs_diff_eq!(g.get_deriv(y)?.as_vector()?, x0);
+    let dx = g.get_deriv(x)?;
+    let dy = g.get_deriv(y)?;
+    assert_abs_diff_eq!(dx.as_vector()?, y0);
+    assert_abs_diff_eq!(dy.as_vector()?, x0);
     Ok(())
 }
@@ -0,0 +1,87 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+// f = x^2 y + sin(y)
+#[test]
+fn test_hessian_scalar() -> Result<()> {
+    let (x0, y0) = (0.5, 1.5);
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", x0)?;
+    let y = g.scalar("y", y0)?;
+    let xx = g.square(x);
+    let xxy = g.mul(xx, y);
+    let s = g.sin(y);
+    let f = g.add(xxy, s);
+
+    let h = g.hessian(f, &[x, y])?;
+    assert_abs_diff_eq!(h[0][0].as_scalar()?, 2.0 * y0, epsilon = 1e-12);
+    assert_abs_diff_eq!(h[0][1].as_scalar()?, 2.0 * x0, epsilon = 1e-12);
+    assert_abs_diff_eq!(h[1][0].as_scalar()?, 2.0 * x0, epsilon = 1e-12);
+    assert_abs_diff_eq!(h[1][1].as_scalar()?, -y0.sin(), epsilon = 1e-12);
+
+    let hv = g.hvp(f, y, 2.0.into_tensor())?;
+    assert_abs_diff_eq!(hv[&x].as_scalar()?, 2.0 * 2.0 * x0, epsilon = 1e-12);
+    assert_abs_diff_eq!(hv[&y].as_scalar()?, -2.0 * y0.sin(), epsilon = 1e-12);
+    Ok(())
+}
+
+// f = (x . x)^2, H = 8 x x^T + 4 (x . x) I
+#[test]
+fn test_hessian_vector() -> Result<()> {
+    let x0 = [1.0, 2.0];
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &x0)?;
+    let xx = g.dot(x, x);
+    let f = g.square(xx);
+    let h = &g.hessian(f, &[x])?[0][0];
+    assert_eq!(h.shape(), &[2, 2]);
+    for i in 0..2 {
+        for j in 0..2 {
+            let delta = if i == j { 1.0 } else { 0.0 };
+            assert_abs_diff_eq!(
+                h[[i, j]],
+                8.0 * x0[i] * x0[j] + 4.0 * 5.0 * delta,
+                epsilon = 1e-12
+            );
+        }
+    }
+    Ok(())
+}
+
+// f = (x . x) y, with z unused
+#[test]
+fn test_hessian_mixed() -> Result<()> {
+    let (x0, y0) = ([1.0, 2.0], 3.0);
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &x0)?;
+    let y = g.scalar("y", y0)?;
+    let z = g.vector("z", &[1.0, 1.0, 1.0])?;
+    let xx = g.dot(x, x);
+    let f = g.mul(xx, y);
+    let h = g.hessian(f, &[x, y, z])?;
+    assert_eq!(h[0][1].shape(), &[2]);
+    assert_eq!(h[1][0].shape(), &[2]);
+    assert_eq!(h[0][2].shape(), &[2, 3]);
+    assert_eq!(h[2][2].shape(), &[3, 3]);
+    for i in 0..2 {
+        for j in 0..2 {
+            let delta = if i == j { 1.0 } else { 0.0 };
+            assert_abs_diff_eq!(h[0][0][[i, j]], 2.0 * y0 * delta, epsilon = 1e-12);
+        }
+        assert_abs_diff_eq!(h[0][1][[i]], 2.0 * x0[i], epsilon = 1e-12);
+        assert_abs_diff_eq!(h[1][0][[i]], 2.0 * x0[i], epsilon = 1e-12);
+    }
+    assert_abs_diff_eq!(h[1][1].as_scalar()?, 0.0, epsilon = 1e-12);
+    assert!(h[0][2].iter().chain(h[2][0].iter()).all(|&v| v == 0.0));
+    assert!(h[2][2].iter().all(|&v| v == 0.0));
+    Ok(())
+}
+
+#[test]
+fn test_hessian_non_scalar() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let y = g.square(x);
+    assert!(g.hessian(y, &[x]).is_err());
+    Ok(())
+}
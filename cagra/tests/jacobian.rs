@@ -0,0 +1,76 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::*, graph::Graph, tensor::*};
+
+// y = x * exp(w) (element-wise), dy_i/dx_j = delta_ij exp(w_j), dy_i/dw_j = delta_ij y_i
+fn check(x0: &[f64], w0: &[f64]) -> Result<()> {
+    let n = x0.len();
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", x0)?;
+    let w = g.vector("w", w0)?;
+    let e = g.exp(w);
+    let y = g.mul(x, e);
+    let jac = g.jacobian(y, &[x, w])?;
+    assert_eq!(jac[0].shape(), &[n, n]);
+    assert_eq!(jac[1].shape(), &[n, n]);
+    for i in 0..n {
+        for j in 0..n {
+            let (dx, dw) = if i == j {
+                (w0[i].exp(), x0[i] * w0[i].exp())
+            } else {
+                (0.0, 0.0)
+            };
+            assert_abs_diff_eq!(jac[0][[i, j]], dx, epsilon = 1e-12);
+            assert_abs_diff_eq!(jac[1][[i, j]], dw, epsilon = 1e-12);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_jacobian_reverse() -> Result<()> {
+    // two inputs of size 3 and an output of size 3, which uses reverse sweeps
+    check(&[1.0, 2.0, 3.0], &[0.1, 0.2, 0.3])
+}
+
+#[test]
+fn test_jacobian_forward() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.5)?;
+    let c = g.constant_vector(&[1.0, 2.0, 3.0]);
+    let y = g.mul(c, x);
+    let z = g.sin(y);
+    let jac = &g.jacobian(z, &[x])?[0];
+    assert_eq!(jac.shape(), &[3]);
+    for (i, c) in [1.0_f64, 2.0, 3.0].iter().enumerate() {
+        assert_abs_diff_eq!(jac[[i]], c * (c * 0.5).cos(), epsilon = 1e-12);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_jacobian_scalar_output() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let y = g.dot(x, x);
+    let jac = &g.jacobian(y, &[x])?[0];
+    assert_eq!(jac.as_vector()?, &[2.0, 4.0]);
+    Ok(())
+}
+
+#[test]
+fn test_jacobian_not_variable() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let e = g.exp(x);
+    // the output of size 1 picks reverse sweeps, and that of size 4 forward sweeps
+    let s = g.dot(e, e);
+    let c = g.constant_vector(&[1.0, 2.0, 3.0, 4.0]);
+    let v = g.mul(c, s);
+    for &y in &[s, v] {
+        match g.jacobian(y, &[x, e]) {
+            Err(Error::NodeTypeError { index }) => assert_eq!(index, e.index()),
+            _ => panic!("Jacobian must be taken with respect to variables"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::*, graph::Graph, tensor::*};
+
+#[test]
+fn test_jvp_scalar() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.2)?;
+    let y = g.scalar("y", 0.7)?;
+    let s = g.sin(x);
+    let e = g.exp(y);
+    let m = g.mul(s, e);
+    let z = g.div(m, x);
+
+    g.eval_value(z)?;
+    g.eval_deriv(z)?;
+    let dx = g.get_deriv(x)?.as_scalar()?;
+    let dy = g.get_deriv(y)?.as_scalar()?;
+
+    let tx = 0.3;
+    let ty = -1.5;
+    let jvp = g
+        .eval_jvp(z, &[(x, tx.into_tensor()), (y, ty.into_tensor())])?
+        .as_scalar()?;
+    assert_abs_diff_eq!(jvp, (dx * tx + dy * ty), epsilon = 1e-12);
+    Ok(())
+}
+
+#[test]
+fn test_jvp_vector() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0, 3.0])?;
+    let c = g.constant_vector(&[2.0, 2.0, 2.0]);
+    let y = g.mul(x, c);
+    let z = g.square(y);
+    let t: &[f64] = &[1.0, 0.0, -1.0];
+    let jvp = g.eval_jvp(z, &[(x, t.into_tensor())])?;
+    // dz_i/dx_i = 8 x_i
+    assert_eq!(jvp.as_vector()?, &[8.0, 0.0, -24.0]);
+    Ok(())
+}
+
+#[test]
+fn test_jvp_independent() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.scalar("y", 1.0)?;
+    let z = g.exp(y);
+    let jvp = g.eval_jvp(z, &[(x, 1.0.into_tensor())])?;
+    assert_eq!(jvp.as_scalar()?, 0.0);
+    Ok(())
+}
+
+#[test]
+fn test_jvp_shape_mismatch() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0, 3.0])?;
+    let y = g.square(x);
+    let t: &[f64] = &[1.0, 0.0];
+    match g.eval_jvp(y, &[(x, t.into_tensor())]) {
+        Err(Error::TensorShapeMismatch { actual, desired }) => {
+            assert_eq!(actual, vec![2]);
+            assert_eq!(desired, vec![3]);
+        }
+        _ => panic!("Shape mismatch is not detected"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_jvp_not_variable() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.exp(x);
+    let z = g.sin(y);
+    match g.eval_jvp(z, &[(y, 1.0.into_tensor())]) {
+        Err(Error::NodeTypeError { index }) => assert_eq!(index, y.index()),
+        _ => panic!("Tangent of an operator is accepted"),
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::*, graph::Graph, tensor::*};
+
+#[test]
+fn test_many() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let z = g.mul(x, y);
+    let s = g.sin(z);
+    let t = g.exp(z);
+    let u = g.add(s, x);
+    g.eval_value(u)?;
+    g.eval_value(t)?;
+
+    g.eval_deriv_with_seed(u, 0.5.into_tensor())?;
+    let ux = g.get_deriv(x)?.as_scalar()?;
+    let uy = g.get_deriv(y)?.as_scalar()?;
+    g.eval_deriv_with_seed(t, 2.0.into_tensor())?;
+    let tx = g.get_deriv(x)?.as_scalar()?;
+    let ty = g.get_deriv(y)?.as_scalar()?;
+
+    g.eval_deriv_many(&[(u, 0.5.into_tensor()), (t, 2.0.into_tensor())])?;
+    assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, ux + tx, epsilon = 1e-12);
+    assert_abs_diff_eq!(g.get_deriv(y)?.as_scalar()?, uy + ty, epsilon = 1e-12);
+    Ok(())
+}
+
+#[test]
+fn test_many_nested() -> Result<()> {
+    // z is an ancestor of w, and listed twice
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let z = g.square(x);
+    let w = g.mul(z, x);
+    g.eval_value(w)?;
+    g.eval_deriv_many(&[
+        (z, 1.0.into_tensor()),
+        (w, 1.0.into_tensor()),
+        (z, 1.0.into_tensor()),
+    ])?;
+    // d/dx (2 x^2 + x^3) = 4x + 3x^2
+    assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, 20.0, epsilon = 1e-12);
+    assert_abs_diff_eq!(g.get_deriv(z)?.as_scalar()?, 4.0, epsilon = 1e-12);
+    Ok(())
+}
+
+#[test]
+fn test_many_shape_mismatch() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0, 3.0])?;
+    let y = g.square(x);
+    let z = g.dot(y, x);
+    g.eval_value(z)?;
+    match g.eval_deriv_many(&[(z, 1.0.into_tensor()), (y, 1.0.into_tensor())]) {
+        Err(Error::TensorShapeMismatch { actual, desired }) => {
+            assert_eq!(actual, Vec::<usize>::new());
+            assert_eq!(desired, vec![3]);
+        }
+        _ => panic!("Shape mismatch is not detected"),
+    }
+    Ok(())
+}
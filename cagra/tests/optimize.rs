@@ -0,0 +1,164 @@
+#[macro_use]
+extern crate cagra;
+
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, tensor::*};
+use ndarray::arr2;
+
+#[test]
+fn test_fold_constants() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 3.0)?;
+    let a = g.constant_scalar(2.0);
+    let b = g.constant_scalar(3.0);
+    let ab = g.mul(a, b);
+    let c = g.exp(ab);
+    let d = g.ln(c);
+    let y = g.mul(d, x);
+    g.set_name(d, "d");
+
+    assert_eq!(g.fold_constants()?, 3);
+    assert!(g[ab].is_constant());
+    assert!(g["d"].is_constant());
+    assert!(!g[y].is_constant());
+    assert_abs_diff_eq!(g.get_value(d)?.as_scalar()?, 6.0, epsilon = 1e-12);
+
+    assert_abs_diff_eq!(g.eval_value(y)?.as_scalar()?, 18.0, epsilon = 1e-12);
+    g.eval_deriv(y)?;
+    assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, 6.0, epsilon = 1e-12);
+
+    // nothing left to fold
+    assert_eq!(g.fold_constants()?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_fold_constants_macro() -> Result<()> {
+    let mut g = graph!(f64, {
+        let x = 1.0;
+        let y = 3.0;
+        let z = x + y + 2.0 * 3.0 * x * y;
+    });
+    let z = g.get_index("z");
+    let before = g.eval_value(z)?.as_scalar()?;
+    assert_eq!(g.fold_constants()?, 1);
+    g.set_value(g.get_index("x"), 2.0.into_tensor())?;
+    assert_eq!(g.eval_value(z)?.as_scalar()?, 5.0 + 36.0);
+    assert_eq!(before, 4.0 + 18.0);
+    Ok(())
+}
+
+#[test]
+fn test_eliminate_common_subexpressions() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let a1 = g.constant_scalar(2.0);
+    let a2 = g.constant_scalar(2.0);
+    let sx1 = g.square(x);
+    let sx2 = g.square(x);
+    let xy = g.mul(x, y);
+    let yx = g.mul(y, x);
+    let d1 = g.div(x, y);
+    let d2 = g.div(y, x);
+    let s1 = g.mul(a1, sx1);
+    let s2 = g.mul(sx2, a2);
+    let p = g.add(s1, xy);
+    let q = g.add(s2, yx);
+    let r = g.add(d1, d2);
+    let z = g.sub(p, q);
+    let w = g.add(z, r);
+    g.set_name(q, "q");
+
+    let remap = g.eliminate_common_subexpressions();
+    assert_eq!(remap[&a1], remap[&a2]);
+    assert_eq!(remap[&sx1], remap[&sx2]);
+    assert_eq!(remap[&xy], remap[&yx]);
+    assert_eq!(remap[&s1], remap[&s2]);
+    assert_eq!(remap[&p], remap[&q]);
+    // div is not commutative
+    assert_ne!(remap[&d1], remap[&d2]);
+    assert_ne!(remap[&x], remap[&y]);
+    assert_eq!(g.get_index("q"), remap[&p]);
+    assert_eq!(g.get_index("x"), remap[&x]);
+
+    let w = remap[&w];
+    let x = remap[&x];
+    let y = remap[&y];
+    let expected = 2.0 / 3.0 + 3.0 / 2.0;
+    assert_abs_diff_eq!(g.eval_value(w)?.as_scalar()?, expected, epsilon = 1e-12);
+    g.eval_deriv(w)?;
+    assert_abs_diff_eq!(
+        g.get_deriv(x)?.as_scalar()?,
+        (1.0 / 3.0 - 3.0 / 4.0),
+        epsilon = 1e-12
+    );
+    assert_abs_diff_eq!(
+        g.get_deriv(y)?.as_scalar()?,
+        (-2.0 / 9.0 + 1.0 / 2.0),
+        epsilon = 1e-12
+    );
+
+    // merged nodes still evaluate after changing the variables
+    g.set_value(x, 1.0.into_tensor())?;
+    assert_abs_diff_eq!(
+        g.eval_value(w)?.as_scalar()?,
+        (1.0 / 3.0 + 3.0),
+        epsilon = 1e-12
+    );
+    Ok(())
+}
+
+#[test]
+fn test_eliminate_signed_zeros() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let pz = g.constant_scalar(0.0);
+    let nz = g.constant_scalar(-0.0);
+    let p = g.div(x, pz);
+    let n = g.div(x, nz);
+    let v1 = g.constant_vector(&[1.0, 2.0]);
+    let v2 = g.constant_vector(&[1.0, 2.0]);
+    let m = g.constant(arr2(&[[1.0, 2.0]]).into_dyn().into_shared());
+
+    let remap = g.eliminate_common_subexpressions();
+    // 0.0 == -0.0 in value, but not in bits
+    assert_ne!(remap[&pz], remap[&nz]);
+    assert_ne!(remap[&p], remap[&n]);
+    assert_eq!(g.eval_value(remap[&p])?.as_scalar()?, f64::INFINITY);
+    assert_eq!(g.eval_value(remap[&n])?.as_scalar()?, f64::NEG_INFINITY);
+    assert_eq!(remap[&v1], remap[&v2]);
+    // same elements in different shapes
+    assert_ne!(remap[&v1], remap[&m]);
+    Ok(())
+}
+
+#[test]
+fn test_prune() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let unused = g.scalar("unused", 4.0)?;
+    let sx = g.square(x);
+    let helper = g.exp(y);
+    let helper2 = g.mul(helper, unused);
+    let z = g.mul(sx, y);
+    g.set_name(helper2, "helper");
+    g.set_name(z, "z");
+
+    let remap = g.prune(&[z]);
+    assert_eq!(remap.len(), 5);
+    assert!(!remap.contains_key(&helper));
+    assert!(!remap.contains_key(&helper2));
+    assert_eq!(g.get_index("x"), remap[&x]);
+    assert_eq!(g.get_index("z"), remap[&z]);
+    assert_eq!(g.get_index("unused"), remap[&unused]);
+    assert_eq!(g.variables().len(), 3);
+
+    let z = remap[&z];
+    assert_eq!(g.eval_value(z)?.as_scalar()?, 12.0);
+    g.eval_deriv(z)?;
+    assert_eq!(g.get_deriv(remap[&x])?.as_scalar()?, 12.0);
+    assert_eq!(g.get_deriv(remap[&y])?.as_scalar()?, 4.0);
+    Ok(())
+}
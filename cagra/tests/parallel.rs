@@ -0,0 +1,67 @@
+#![cfg(feature = "parallel")]
+
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+// Wide graph with many independent branches sharing the variables
+fn wide_graph() -> Result<(
+    Graph<f64>,
+    Vec<petgraph::graph::NodeIndex>,
+    petgraph::graph::NodeIndex,
+)> {
+    let mut g = Graph::new();
+    let x = g.vector("x", &[0.1, 0.2, 0.3])?;
+    let y = g.vector("y", &[1.1, -0.7, 0.5])?;
+    let mut terms = Vec::new();
+    for i in 0..32 {
+        let c = g.constant_vector(&[i as f64 * 0.1, 1.0, -(i as f64) * 0.01]);
+        let a = g.mul(x, c);
+        let s = g.sin(a);
+        let e = g.exp(y);
+        let m = g.mul(s, e);
+        let t = g.tanh(m);
+        terms.push(g.dot(t, x));
+    }
+    let mut z = terms[0];
+    for &t in &terms[1..] {
+        z = g.add(z, t);
+    }
+    Ok((g, vec![x, y], z))
+}
+
+#[test]
+fn test_parallel_matches_serial() -> Result<()> {
+    let (mut serial, vars, z) = wide_graph()?;
+    let mut parallel = serial.clone();
+
+    let v_serial = serial.eval_value(z)?;
+    let v_parallel = parallel.eval_value_par(z)?;
+    assert_eq!(
+        v_serial.as_scalar()?.to_bits(),
+        v_parallel.as_scalar()?.to_bits()
+    );
+
+    serial.eval_deriv(z)?;
+    parallel.eval_deriv_par(z)?;
+    for var in vars {
+        let d_serial = serial.get_deriv(var)?;
+        let d_parallel = parallel.get_deriv(var)?;
+        for (a, b) in d_serial.iter().zip(d_parallel.iter()) {
+            assert_eq!(a.to_bits(), b.to_bits());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parallel_dirty() -> Result<()> {
+    let (mut g, vars, z) = wide_graph()?;
+    g.eval_value_par(z)?;
+    let x1: &[f64] = &[0.4, 0.5, 0.6];
+    g.set_value(vars[0], x1.into_tensor())?;
+    let v_parallel = g.eval_value_par(z)?;
+
+    let (mut serial, vars, z) = wide_graph()?;
+    serial.set_value(vars[0], x1.into_tensor())?;
+    assert_eq!(serial.eval_value(z)?, v_parallel);
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use approx::assert_abs_diff_eq;
+use cagra::{
+    error::{Error, Result},
+    graph::Graph,
+    tensor::*,
+};
+
+#[test]
+fn test_replace_operand() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 6.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let w = g.scalar("w", 2.0)?;
+    let d = g.div(x, y);
+    let z = g.exp(d);
+    assert_abs_diff_eq!(g.eval_value(d)?.as_scalar()?, 2.0, epsilon = 1e-12);
+
+    // the order of the operands is kept
+    g.replace_operand(d, y, w)?;
+    assert!(g.get_value(z).is_err());
+    assert_abs_diff_eq!(g.eval_value(d)?.as_scalar()?, 3.0, epsilon = 1e-12);
+    g.replace_operand(d, x, y)?;
+    assert_abs_diff_eq!(g.eval_value(d)?.as_scalar()?, 1.5, epsilon = 1e-12);
+    g.eval_deriv(d)?;
+    assert_abs_diff_eq!(g.get_deriv(w)?.as_scalar()?, -0.75, epsilon = 1e-12);
+
+    match g.replace_operand(d, x, w) {
+        Err(Error::OperandNotFound { .. }) => {}
+        _ => panic!("x is no longer an operand"),
+    }
+    match g.replace_operand(d, y, z) {
+        Err(Error::CyclicGraph { .. }) => {}
+        _ => panic!("z depends on d"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_replace_node() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.5)?;
+    let w = g.scalar("w", 2.0)?;
+    let h = g.mul(x, w);
+    let act = g.tanh(h);
+    let z = g.mul(act, act);
+    g.set_name(act, "act");
+    assert_abs_diff_eq!(
+        g.eval_value(z)?.as_scalar()?,
+        1.0f64.tanh().powi(2),
+        epsilon = 1e-12
+    );
+
+    // swap the activation
+    let sinh = g.sinh(h);
+    g.replace_node(act, sinh)?;
+    assert_eq!(g.get_index("act"), sinh);
+    assert_abs_diff_eq!(
+        g.eval_value(z)?.as_scalar()?,
+        1.0f64.sinh().powi(2),
+        epsilon = 1e-12
+    );
+
+    // freeze the subtree into a constant
+    let frozen = g.eval_value(sinh)?;
+    let c = g.constant(frozen);
+    g.replace_node(sinh, c)?;
+    g.set_value(x, 2.0.into_tensor())?;
+    assert_abs_diff_eq!(
+        g.eval_value(z)?.as_scalar()?,
+        1.0f64.sinh().powi(2),
+        epsilon = 1e-12
+    );
+    g.eval_deriv(z)?;
+    assert!(g.get_deriv(x).is_err());
+
+    let e = g.exp(c);
+    match g.replace_node(c, e) {
+        Err(Error::CyclicGraph { .. }) => {}
+        _ => panic!("e depends on c"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_remove_node() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.scalar("y", 2.0)?;
+    let e = g.exp(x);
+    let z = g.add(e, y);
+    g.set_name(e, "e");
+
+    match g.remove_node(e) {
+        Err(Error::NodeInUse { .. }) => {}
+        _ => panic!("e is used by z"),
+    }
+    let s = g.sin(y);
+    g.replace_node(e, s)?;
+    g.remove_node(e)?;
+    assert_eq!(g.get_index("e"), s);
+    g.remove_node(x)?;
+    assert_eq!(g.variables(), vec![y]);
+
+    // other indices are kept valid
+    assert_abs_diff_eq!(
+        g.eval_value(z)?.as_scalar()?,
+        2.0f64.sin() + 2.0,
+        epsilon = 1e-12
+    );
+    g.eval_deriv(z)?;
+    assert_abs_diff_eq!(
+        g.get_deriv(y)?.as_scalar()?,
+        2.0f64.cos() + 1.0,
+        epsilon = 1e-12
+    );
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use approx::assert_abs_diff_eq;
+use cagra::{
+    error::{Error, Result},
+    graph::Graph,
+    operator::{Binary, Unary},
+    rewrite::{Pattern, Rewriter},
+    tensor::*,
+};
+
+#[test]
+fn test_simplify_identities() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let one = g.constant_scalar(1.0);
+    let zero = g.constant_scalar(0.0);
+    let x1 = g.mul(x, one);
+    let x10 = g.add(zero, x1);
+    let n = g.neg(y);
+    let nn = g.neg(n);
+    let e = g.exp(nn);
+    let le = g.ln(e);
+    let z = g.mul(x10, le);
+    g.set_name(x10, "x10");
+
+    let remap = g.simplify();
+    assert_eq!(remap[&x1], x);
+    assert_eq!(remap[&x10], x);
+    assert_eq!(remap[&nn], y);
+    assert_eq!(remap[&le], y);
+    assert!(!remap.contains_key(&z));
+    assert_eq!(g.get_index("x10"), x);
+
+    assert_abs_diff_eq!(g.eval_value(z)?.as_scalar()?, 6.0, epsilon = 1e-12);
+    g.eval_deriv(z)?;
+    assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, 3.0, epsilon = 1e-12);
+    assert_abs_diff_eq!(g.get_deriv(y)?.as_scalar()?, 2.0, epsilon = 1e-12);
+    // rewritten nodes are left in the graph, and rewritten again into the same nodes
+    assert_eq!(g.simplify(), remap);
+    Ok(())
+}
+
+#[test]
+fn test_simplify_cancellation() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let y = g.vector("y", &[3.0, 4.0])?;
+    let sx = g.square(x);
+    let d = g.div(sx, sx);
+    let s = g.sub(y, y);
+    let z = g.add(d, s);
+    let w = g.mul(z, y);
+    g.eval_value(w)?;
+
+    let remap = g.simplify();
+    assert!(g[remap[&d]].is_constant());
+    assert_eq!(g.get_value(remap[&d])?.as_vector()?, &[1.0, 1.0]);
+    assert!(g[remap[&s]].is_constant());
+    assert_eq!(g.get_value(remap[&s])?.as_vector()?, &[0.0, 0.0]);
+
+    g.set_value(y, (&[5.0, 6.0][..]).into_tensor())?;
+    assert_eq!(g.eval_value(w)?.as_vector()?, &[5.0, 6.0]);
+    Ok(())
+}
+
+#[test]
+fn test_user_rule() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.5)?;
+    let s = g.sin(x);
+    let c = g.cos(x);
+    let ss = g.square(s);
+    let cc = g.square(c);
+    let z = g.add(ss, cc);
+    let w = g.mul(z, x);
+
+    // sin(x)^2 + cos(x)^2 = 1
+    let t = || Pattern::Any(0);
+    let square = |op| Pattern::unary(Unary::Square, Pattern::unary(op, t()));
+    let mut rewriter = Rewriter::new();
+    rewriter.add_pattern(
+        Pattern::binary(Binary::Add, square(Unary::Sin), square(Unary::Cos)),
+        Pattern::Scalar(1.0),
+    )?;
+    let remap = rewriter.rewrite(&mut g);
+    // `1 * x` is then simplified by the built-in rule
+    assert_eq!(remap[&w], x);
+    assert_abs_diff_eq!(g.eval_value(remap[&w])?.as_scalar()?, 0.5, epsilon = 1e-12);
+
+    let unbound = rewriter.add_pattern(Pattern::unary(Unary::Exp, t()), Pattern::Any(1));
+    match unbound {
+        Err(Error::UnboundWildcard { id }) => assert_eq!(id, 1),
+        _ => panic!("Unbound wildcard must be rejected"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_simplify_gradient() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let xy = g.mul(x, y);
+    let z = g.sub(xy, y);
+    let grad = g.grad(z)?;
+    let dx = grad[&x];
+    let dy = grad[&y];
+    let expected = (
+        g.eval_value(dx)?.as_scalar()?,
+        g.eval_value(dy)?.as_scalar()?,
+    );
+
+    let remap = g.simplify();
+    assert!(!remap.is_empty());
+    let dx = remap.get(&dx).cloned().unwrap_or(dx);
+    let dy = remap.get(&dy).cloned().unwrap_or(dy);
+    g.set_value(x, 4.0.into_tensor())?;
+    g.set_value(y, 5.0.into_tensor())?;
+    assert_abs_diff_eq!(g.eval_value(dx)?.as_scalar()?, 5.0, epsilon = 1e-12);
+    assert_abs_diff_eq!(g.eval_value(dy)?.as_scalar()?, 3.0, epsilon = 1e-12);
+    assert_eq!(expected, (3.0, 1.0));
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use cagra::{error::*, graph::Graph, tensor::*};
+
+#[test]
+fn test_seed() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0, 3.0])?;
+    let y = g.square(x);
+    g.eval_value(y)?;
+    let seed: &[f64] = &[1.0, -1.0, 0.5];
+    g.eval_deriv_with_seed(y, seed.into_tensor())?;
+    assert_eq!(g.get_deriv(x)?.as_vector()?, &[2.0, -4.0, 3.0]);
+    Ok(())
+}
+
+#[test]
+fn test_seed_shape_mismatch() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0, 3.0])?;
+    let y = g.square(x);
+    g.eval_value(y)?;
+    match g.eval_deriv_with_seed(y, 1.0.into_tensor()) {
+        Err(Error::TensorShapeMismatch { actual, desired }) => {
+            assert_eq!(actual, Vec::<usize>::new());
+            assert_eq!(desired, vec![3]);
+        }
+        _ => panic!("Shape mismatch is not detected"),
+    }
+    Ok(())
+}
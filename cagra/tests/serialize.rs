@@ -0,0 +1,90 @@
+use cagra::{
+    error::{Error, Result},
+    graph::Graph,
+    serialize::Registry,
+    tensor::*,
+};
+use serde_json::{json, Value};
+
+/// JSON of `x * exp(x)`, and the positions of the unary and binary nodes
+fn data() -> Result<(Value, usize, usize)> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.exp(x);
+    let z = g.mul(x, y);
+    g.set_name(z, "z");
+    let data: Value = serde_json::from_str(&g.to_json()?).unwrap();
+    let position = |kind: &str| {
+        data["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .position(|node| node["kind"].get(kind).is_some())
+            .unwrap()
+    };
+    let (unary, binary) = (position("Unary"), position("Binary"));
+    Ok((data, unary, binary))
+}
+
+fn load(data: &Value) -> Result<Graph<f64>> {
+    Graph::from_json(&data.to_string(), &Registry::new())
+}
+
+fn assert_malformed_node(data: &Value, expected: usize) {
+    match load(data) {
+        Err(Error::MalformedNode { position }) => assert_eq!(position, expected),
+        _ => panic!("Malformed node must be rejected"),
+    }
+}
+
+#[test]
+fn test_round_trip() -> Result<()> {
+    let (data, _, _) = data()?;
+    let mut g = load(&data)?;
+    let z = g.get_index("z");
+    assert_eq!(g.eval_value(z)?.as_scalar()?, 2.0 * 2.0f64.exp());
+    Ok(())
+}
+
+#[test]
+fn test_argument_out_of_range() -> Result<()> {
+    let (mut data, unary, _) = data()?;
+    data["nodes"][unary]["args"] = json!([100]);
+    assert_malformed_node(&data, unary);
+    Ok(())
+}
+
+#[test]
+fn test_forward_reference() -> Result<()> {
+    let (base, unary, binary) = data()?;
+    for &arg in &[unary, binary] {
+        let mut data = base.clone();
+        data["nodes"][unary]["args"] = json!([arg]);
+        assert_malformed_node(&data, unary);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_wrong_arity() -> Result<()> {
+    let (base, unary, binary) = data()?;
+    let x = base["nodes"][binary]["args"][0].clone();
+    let cases = [(unary, json!([])), (binary, json!([x])), (0, json!([0]))];
+    for (position, args) in cases.iter() {
+        let mut data = base.clone();
+        data["nodes"][*position]["args"] = args.clone();
+        assert_malformed_node(&data, *position);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_name_out_of_range() -> Result<()> {
+    let (mut data, _, _) = data()?;
+    data["names"]["z"] = json!(100);
+    match load(&data) {
+        Err(Error::MalformedName { name }) => assert_eq!(name, "z"),
+        _ => panic!("Name of a missing node must be rejected"),
+    }
+    Ok(())
+}
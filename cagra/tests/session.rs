@@ -0,0 +1,91 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, session::Session, tensor::*};
+use std::{sync::Arc, thread};
+
+#[test]
+fn test_session_independent() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let xy = g.mul(x, y);
+    let z = g.square(xy);
+    let g = Arc::new(g);
+
+    let mut s1 = Session::new(g.clone());
+    let mut s2 = Session::new(g.clone());
+    s2.set_value(x, 1.0.into_tensor())?;
+
+    assert_eq!(s1.eval_value(z)?.as_scalar()?, 36.0);
+    assert_eq!(s2.eval_value(z)?.as_scalar()?, 9.0);
+    s1.eval_deriv(z)?;
+    s2.eval_deriv(z)?;
+    assert_eq!(s1.get_deriv(x)?.as_scalar()?, 36.0);
+    assert_eq!(s2.get_deriv(x)?.as_scalar()?, 18.0);
+
+    // the graph itself is untouched
+    assert_eq!(g.get_value(x)?.as_scalar()?, 2.0);
+    Ok(())
+}
+
+#[test]
+fn test_session_threads() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.0)?;
+    let s = g.sin(x);
+    let y = g.square(s);
+    let g = Arc::new(g);
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let g = g.clone();
+            thread::spawn(move || -> Result<(f64, f64)> {
+                let mut session = Session::new(g);
+                session.set_value(x, (i as f64).into_tensor())?;
+                let value = session.eval_value(y)?.as_scalar()?;
+                session.eval_deriv(y)?;
+                Ok((value, session.get_deriv(x)?.as_scalar()?))
+            })
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let (value, deriv) = handle.join().unwrap()?;
+        let x0 = i as f64;
+        assert_abs_diff_eq!(value, x0.sin().powi(2), epsilon = 1e-12);
+        assert_abs_diff_eq!(deriv, (2.0 * x0).sin(), epsilon = 1e-12);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_session_type_error() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.0)?;
+    let y = g.exp(x);
+    let mut s = Session::new(Arc::new(g));
+    assert!(s.set_value(y, 1.0.into_tensor()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_session_accumulate() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let y = g.square(x);
+    let z = g.sin(y);
+    let g = Arc::new(g);
+
+    let mut s = Session::new(g.clone());
+    assert!(!s.is_accumulating());
+    s.set_accumulate(true);
+    let mut expected = 0.0;
+    for &v in &[1.0, 2.0] {
+        s.set_value(x, v.into_tensor())?;
+        s.eval_value(z)?;
+        s.eval_deriv(z)?;
+        expected += (v * v).cos() * 2.0 * v;
+        assert_abs_diff_eq!(s.get_deriv(x)?.as_scalar()?, expected, epsilon = 1e-12);
+    }
+    s.zero_grad();
+    assert!(s.get_deriv(x).is_err());
+    Ok(())
+}
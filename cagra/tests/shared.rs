@@ -0,0 +1,35 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+// y = x + x, z = y + y, ...
+// Walking every path from the output would visit x 2^n times.
+#[test]
+fn test_shared_subexpression() -> Result<()> {
+    let n = 100;
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let mut y = x;
+    for _ in 0..n {
+        y = g.add(y, y);
+    }
+    assert_eq!(g.eval_value(y)?.as_scalar()?, 2.0_f64.powi(n));
+    g.eval_deriv(y)?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 2.0_f64.powi(n));
+    Ok(())
+}
+
+#[test]
+fn test_multiple_consumers() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let s = g.square(x);
+    let a = g.mul(s, x);
+    let b = g.exp(s);
+    let z = g.add(a, b);
+    g.eval_value(z)?;
+    g.eval_deriv(z)?;
+    // z = x^3 + exp(x^2)
+    let expected = 3.0 * 4.0 + 4.0 * 4.0_f64.exp();
+    assert_abs_diff_eq!(g.get_deriv(x)?.as_scalar()?, expected, epsilon = 1e-9);
+    Ok(())
+}
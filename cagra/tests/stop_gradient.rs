@@ -0,0 +1,67 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, session::Session, tensor::*};
+use ndarray::arr1;
+use std::sync::Arc;
+
+#[test]
+fn test_stop_gradient() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 3.0)?;
+    let y = g.scalar("y", 2.0)?;
+    let sx = g.stop_gradient(x);
+    let z = g.mul(x, sx);
+    let sy = g.stop_gradient(y);
+    let w = g.add(z, sy);
+    assert_eq!(g.eval_value(sx)?.as_scalar()?, 3.0);
+    assert_eq!(g.eval_value(w)?.as_scalar()?, 11.0);
+
+    g.eval_deriv(w)?;
+    assert_eq!(g.get_deriv(x)?.as_scalar()?, 3.0);
+    assert!(g.get_deriv(y).is_err());
+
+    let jvp = g.eval_jvp(w, &[(x, 1.0.into_tensor()), (y, 1.0.into_tensor())])?;
+    assert_eq!(jvp.as_scalar()?, 3.0);
+
+    let grad = g.grad(w)?;
+    assert!(!grad.contains_key(&y));
+    assert_eq!(g.eval_value(grad[&x])?.as_scalar()?, 3.0);
+
+    let mut tape = g.compile(&[w])?;
+    tape.forward()?;
+    tape.backward(w)?;
+    assert_eq!(tape.get_deriv(x)?.as_scalar()?, 3.0);
+    assert!(tape.get_deriv(y).is_err());
+
+    let mut session = Session::new(Arc::new(g));
+    session.eval_value(w)?;
+    session.eval_deriv(w)?;
+    assert_eq!(session.get_deriv(x)?.as_scalar()?, 3.0);
+    Ok(())
+}
+
+#[test]
+fn test_straight_through_estimator() -> Result<()> {
+    // value of tanh(x), derivative of identity
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[0.5, -1.0])?;
+    let t = g.tanh(x);
+    let d = g.sub(t, x);
+    let sd = g.stop_gradient(d);
+    let y = g.add(x, sd);
+    let w = g.constant_vector(&[2.0, 3.0]);
+    let f = g.dot(w, y);
+
+    let expected = 2.0 * 0.5f64.tanh() + 3.0 * (-1.0f64).tanh();
+    assert_abs_diff_eq!(g.eval_value(f)?.as_scalar()?, expected, epsilon = 1e-12);
+    g.eval_deriv(f)?;
+    assert_eq!(g.get_deriv(x)?.as_vector()?, &[2.0, 3.0]);
+
+    let xs = arr1(&[0.5, -1.0, 1.0, 2.0])
+        .into_shape((2, 2))
+        .unwrap()
+        .into_dyn()
+        .into_shared();
+    let derivs = g.eval_deriv_batch(f, &[(x, xs)])?;
+    assert_eq!(derivs[&x].as_slice().unwrap(), &[2.0, 3.0, 2.0, 3.0]);
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::Result, graph::Graph, tensor::*};
+
+#[test]
+fn test_grad_matches_eval_deriv() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.8)?;
+    let y = g.scalar("y", 1.3)?;
+    let xy = g.mul(x, y);
+    let t = g.tanh(xy);
+    let l = g.ln(y);
+    let d = g.div(t, l);
+    let c = g.cos(x);
+    let z = g.sub(d, c);
+
+    let grad = g.grad(z)?;
+    g.eval_deriv(z)?;
+    for &var in &[x, y] {
+        let symbolic = g.eval_value(grad[&var])?.as_scalar()?;
+        assert_abs_diff_eq!(symbolic, g.get_deriv(var)?.as_scalar()?, epsilon = 1e-12);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_second_derivative() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x0 = 0.4;
+    let x = g.scalar("x", x0)?;
+    let y = g.sin(x);
+    let dy = g.grad(y)?[&x];
+    let ddy = g.grad(dy)?[&x];
+    let dddy = g.grad(ddy)?[&x];
+    assert_abs_diff_eq!(g.eval_value(dy)?.as_scalar()?, x0.cos(), epsilon = 1e-12);
+    assert_abs_diff_eq!(g.eval_value(ddy)?.as_scalar()?, -x0.sin(), epsilon = 1e-12);
+    assert_abs_diff_eq!(g.eval_value(dddy)?.as_scalar()?, -x0.cos(), epsilon = 1e-12);
+
+    // derivative nodes follow the variable
+    g.set_value(x, 1.0.into_tensor())?;
+    assert_abs_diff_eq!(
+        g.eval_value(ddy)?.as_scalar()?,
+        -1.0_f64.sin(),
+        epsilon = 1e-12
+    );
+    Ok(())
+}
+
+#[test]
+fn test_grad_dot() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let y = g.vector("y", &[3.0, 4.0])?;
+    let sx = g.square(x);
+    let z = g.dot(sx, y);
+    let grad = g.grad(z)?;
+    assert_eq!(g.eval_value(grad[&x])?.as_vector()?, &[6.0, 16.0]);
+    assert_eq!(g.eval_value(grad[&y])?.as_vector()?, &[1.0, 4.0]);
+    Ok(())
+}
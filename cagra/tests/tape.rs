@@ -0,0 +1,112 @@
+use cagra::{error::*, graph::Graph, tensor::*};
+
+#[test]
+fn test_tape_matches_graph() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[0.1, 0.2, 0.3])?;
+    let y = g.vector("y", &[1.0, 2.0, 3.0])?;
+    let s = g.sin(x);
+    let e = g.exp(y);
+    let m = g.mul(s, e);
+    let d = g.div(m, y);
+    let z = g.dot(d, x);
+    let mut tape = g.compile(&[z])?;
+
+    for i in 0..3 {
+        let x0: &[f64] = &[0.1 * i as f64, 0.5, -0.2];
+        g.set_value(x, x0.into_tensor())?;
+        tape.set_value(x, x0.into_tensor())?;
+
+        let expected = g.eval_value(z)?.as_scalar()?;
+        g.eval_deriv(z)?;
+        tape.forward()?;
+        tape.backward(z)?;
+        assert_eq!(tape.get_value(z)?.as_scalar()?, expected);
+        assert_eq!(tape.get_deriv(x)?, g.get_deriv(x)?);
+        assert_eq!(tape.get_deriv(y)?, g.get_deriv(y)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_tape_values_not_overwritten() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let y = g.square(x);
+    let mut tape = g.compile(&[y])?;
+    tape.forward()?;
+    let y0 = tape.get_value(y)?;
+
+    let x1: &[f64] = &[3.0, 4.0];
+    tape.set_value(x, x1.into_tensor())?;
+    tape.forward()?;
+    assert_eq!(y0.as_vector()?, &[1.0, 4.0]);
+    assert_eq!(tape.get_value(y)?.as_vector()?, &[9.0, 16.0]);
+    Ok(())
+}
+
+#[test]
+fn test_tape_errors() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 1.0)?;
+    let w = g.scalar("w", 1.0)?;
+    let y = g.exp(x);
+    let mut tape = g.compile(&[y])?;
+    match tape.set_value(y, 1.0.into_tensor()) {
+        Err(Error::NodeTypeError { .. }) => {}
+        _ => panic!("Operator node must not be set"),
+    }
+    match tape.set_value(w, 1.0.into_tensor()) {
+        Err(Error::NodeNotCompiled { .. }) => {}
+        _ => panic!("Node out of the tape must not be set"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_tape_reuses_slots() -> Result<()> {
+    // y_{k+1} = -(y_k + x)
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let mut y = x;
+    let mut hidden = Vec::new();
+    for _ in 0..10 {
+        let s = g.add(y, x);
+        y = g.neg(s);
+        hidden.push(s);
+    }
+    let mut tape = g.compile(&[y])?;
+    // 21 nodes share the slots of `x`, `s` and `y`
+    assert_eq!(tape.num_slots(), 3);
+
+    tape.forward()?;
+    tape.backward(y)?;
+    g.eval_value(y)?;
+    g.eval_deriv(y)?;
+    assert_eq!(tape.get_value(y)?, g.get_value(y)?);
+    assert_eq!(tape.get_deriv(x)?, g.get_deriv(x)?);
+    match tape.get_value(hidden[0]) {
+        Err(Error::ValueReleased { .. }) => {}
+        _ => panic!("Released value must not be read"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_tape_keeps_values_for_backward() -> Result<()> {
+    // every intermediate value is read by the derivative of `exp`
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 0.1)?;
+    let mut y = x;
+    for _ in 0..5 {
+        y = g.exp(y);
+    }
+    let mut tape = g.compile(&[y])?;
+    assert_eq!(tape.num_slots(), 6);
+    tape.forward()?;
+    tape.backward(y)?;
+    g.eval_value(y)?;
+    g.eval_deriv(y)?;
+    assert_eq!(tape.get_deriv(x)?, g.get_deriv(x)?);
+    Ok(())
+}
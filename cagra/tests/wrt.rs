@@ -0,0 +1,63 @@
+use approx::assert_abs_diff_eq;
+use cagra::{error::*, graph::Graph, tensor::*};
+
+#[test]
+fn test_wrt() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.scalar("y", 3.0)?;
+    let w = g.scalar("w", 0.5)?;
+    let xy = g.mul(x, y);
+    let s = g.sin(xy);
+    let c = g.constant_scalar(4.0);
+    let e = g.exp(c);
+    let t = g.mul(e, w);
+    let z = g.add(s, t);
+    g.eval_value(z)?;
+
+    let derivs = g.eval_deriv_wrt(z, &[x, w])?;
+    assert_eq!(derivs.len(), 2);
+    g.eval_deriv(z)?;
+    assert_abs_diff_eq!(
+        derivs[&x].as_scalar()?,
+        g.get_deriv(x)?.as_scalar()?,
+        epsilon = 1e-12
+    );
+    assert_abs_diff_eq!(
+        derivs[&w].as_scalar()?,
+        g.get_deriv(w)?.as_scalar()?,
+        epsilon = 1e-12
+    );
+    assert_abs_diff_eq!(derivs[&x].as_scalar()?, 6.0f64.cos() * 3.0, epsilon = 1e-12);
+    assert_abs_diff_eq!(derivs[&w].as_scalar()?, 4.0f64.exp(), epsilon = 1e-12);
+    Ok(())
+}
+
+#[test]
+fn test_wrt_keeps_derivs() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.vector("x", &[1.0, 2.0])?;
+    let y = g.vector("y", &[3.0, 4.0])?;
+    let z = g.dot(x, x);
+    g.eval_value(z)?;
+    assert!(g.eval_deriv_wrt(z, &[x])?.contains_key(&x));
+    assert!(g.get_deriv(x).is_err());
+
+    // z does not depend on y
+    let derivs = g.eval_deriv_wrt(z, &[y])?;
+    assert_eq!(derivs[&y].as_vector()?, &[0.0, 0.0]);
+    Ok(())
+}
+
+#[test]
+fn test_wrt_not_variable() -> Result<()> {
+    let mut g: Graph<f64> = Graph::new();
+    let x = g.scalar("x", 2.0)?;
+    let y = g.square(x);
+    g.eval_value(y)?;
+    match g.eval_deriv_wrt(y, &[y]) {
+        Err(Error::NodeTypeError { index }) => assert_eq!(index, y.index()),
+        _ => panic!("Operator is accepted as a variable"),
+    }
+    Ok(())
+}